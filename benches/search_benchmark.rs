@@ -2,7 +2,7 @@ use criterion::measurement::WallTime;
 use criterion::{BenchmarkGroup, Criterion, criterion_group, criterion_main};
 use rand::Rng;
 use rand::distr::Alphanumeric;
-use search_rs::{Config, Search};
+use search_rs::{Config, MatchMode, Search};
 use std::io::Write;
 use std::time::Duration;
 use tempfile::NamedTempFile;
@@ -53,6 +53,8 @@ fn search_benchmarks(c: &mut Criterion) {
     benchmark_small_file_low_freq_case_insensitive(&mut group);
     benchmark_small_file_high_freq(&mut group);
     benchmark_large_file_low_freq(&mut group);
+    benchmark_large_file_low_freq_regex(&mut group);
+    benchmark_large_file_low_freq_case_insensitive(&mut group);
 
     group.finish()
 }
@@ -71,8 +73,8 @@ fn benchmark_small_file_low_freq(group: &mut BenchmarkGroup<WallTime>) {
                 None,
                 None,
             );
-            let search = Search::new(config);
-            search.search().unwrap();
+            let search = Search::new(config).unwrap();
+            search.run().unwrap();
         })
     });
 }
@@ -90,8 +92,8 @@ fn benchmark_small_file_high_freq(group: &mut BenchmarkGroup<WallTime>) {
                 None,
                 None,
             );
-            let search = Search::new(config);
-            search.search().unwrap();
+            let search = Search::new(config).unwrap();
+            search.run().unwrap();
         })
     });
 }
@@ -110,8 +112,8 @@ fn benchmark_small_file_low_freq_case_insensitive(group: &mut BenchmarkGroup<Wal
                 None,
                 None,
             );
-            let search = Search::new(config);
-            search.search().unwrap();
+            let search = Search::new(config).unwrap();
+            search.run().unwrap();
         })
     });
 }
@@ -132,8 +134,69 @@ fn benchmark_large_file_low_freq(group: &mut BenchmarkGroup<WallTime>) {
                 None,
                 None,
             );
-            let search = Search::new(config);
-            search.search().unwrap();
+            let search = Search::new(config).unwrap();
+            search.run().unwrap();
+        })
+    });
+
+    group.bench_function("benchmark_larg_file_low_freq_parallel", |b| {
+        b.iter(|| {
+            let config = Config::init(
+                file_path.to_path_buf(),
+                MATCH_TERM.to_string(),
+                Some(false),
+                Some(search_rs::FindAction::Boolean),
+                None,
+                Some(4),
+            );
+            let search = Search::new(config).unwrap();
+            search.run().unwrap();
+        })
+    });
+}
+
+// The regex has a required literal, so most lines are rejected by the prefilter alone
+fn benchmark_large_file_low_freq_regex(group: &mut BenchmarkGroup<WallTime>) {
+    let tmp_file = create_tmp_file(1000000, 0.001, MATCH_TERM);
+    let file_path = tmp_file.path().to_path_buf();
+
+    group.measurement_time(Duration::from_secs(14));
+
+    group.bench_function("benchmark_large_file_low_freq_regex", |b| {
+        b.iter(|| {
+            let config = Config::init(
+                file_path.to_path_buf(),
+                format!("[0-9]{MATCH_TERM}[a-z]"),
+                Some(false),
+                Some(search_rs::FindAction::Count),
+                None,
+                None,
+            )
+            .with_match_mode(MatchMode::Regex);
+            let search = Search::new(config).unwrap();
+            search.run().unwrap();
+        })
+    });
+}
+
+fn benchmark_large_file_low_freq_case_insensitive(group: &mut BenchmarkGroup<WallTime>) {
+    let tmp_file = create_tmp_file(1000000, 0.001, MATCH_TERM);
+    let file_path = tmp_file.path().to_path_buf();
+
+    group.measurement_time(Duration::from_secs(14));
+
+    group.bench_function("benchmark_large_file_low_freq_case_insensitive", |b| {
+        b.iter(|| {
+            let config = Config::init(
+                file_path.to_path_buf(),
+                MATCH_TERM.to_uppercase(),
+                Some(true),
+                Some(search_rs::FindAction::Count),
+                None,
+                None,
+            );
+            let search = Search::new(config).unwrap();
+            search.run().unwrap();
         })
     });
 }
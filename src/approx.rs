@@ -0,0 +1,200 @@
+use crate::SearchError;
+use regex_syntax::hir::{Class, Hir, HirKind};
+
+// Beyond this the regex is rejected rather than simulated, as bounded repetitions are expanded
+const MAX_STATES: usize = 10_000;
+
+// agrep-style approximate regex matching: a line matches when some part of it is within
+// `max_errors` insertions, deletions and substitutions of a string the regex matches.
+// The regex is compiled to a Thompson NFA, which is simulated with one set of active states per
+// number of errors made so far (Wu and Manber), so the cost is linear in the line length.
+pub(crate) struct ApproxRegex {
+    states: Vec<State>,
+    start: usize,
+    max_errors: usize,
+}
+
+enum State {
+    // Consumes one character in any of the ranges
+    Ranges(Vec<(char, char)>, usize),
+    Split(usize, usize),
+    Match,
+}
+
+impl ApproxRegex {
+    pub(crate) fn new(
+        patterns: &[String],
+        case_insensitive: bool,
+        max_errors: usize,
+    ) -> Result<ApproxRegex, SearchError> {
+        let invalid = |pattern: &str, reason: &str| {
+            SearchError::InitializationError(format!(
+                "invalid approximate regex {pattern}: {reason}"
+            ))
+        };
+        let mut alternatives = Vec::new();
+        for pattern in patterns.iter() {
+            let hir = regex_syntax::ParserBuilder::new()
+                .case_insensitive(case_insensitive)
+                .build()
+                .parse(pattern)
+                .map_err(|err| invalid(pattern, &err.to_string()))?;
+            alternatives.push(hir);
+        }
+        let mut regex = ApproxRegex {
+            states: vec![State::Match],
+            start: 0,
+            max_errors,
+        };
+        regex.start = regex
+            .compile(&Hir::alternation(alternatives), 0)
+            .map_err(|reason| invalid(&patterns.join("|"), &reason))?;
+        Ok(regex)
+    }
+
+    fn push(&mut self, state: State) -> Result<usize, String> {
+        if self.states.len() >= MAX_STATES {
+            return Err("the expanded pattern is too large".to_string());
+        }
+        self.states.push(state);
+        Ok(self.states.len() - 1)
+    }
+
+    // Compiles back to front: returns the state that matches `hir` and then continues at `next`
+    fn compile(&mut self, hir: &Hir, next: usize) -> Result<usize, String> {
+        match hir.kind() {
+            HirKind::Empty => Ok(next),
+            HirKind::Literal(literal) => {
+                let text = std::str::from_utf8(&literal.0)
+                    .map_err(|_| "byte literals aren't supported".to_string())?;
+                text.chars()
+                    .rev()
+                    .try_fold(next, |next, c| self.push(State::Ranges(vec![(c, c)], next)))
+            }
+            HirKind::Class(Class::Unicode(class)) => {
+                let ranges = class
+                    .ranges()
+                    .iter()
+                    .map(|range| (range.start(), range.end()))
+                    .collect();
+                self.push(State::Ranges(ranges, next))
+            }
+            HirKind::Class(Class::Bytes(_)) => Err("byte classes aren't supported".to_string()),
+            HirKind::Look(_) => Err("anchors and word boundaries aren't supported".to_string()),
+            HirKind::Capture(capture) => self.compile(&capture.sub, next),
+            HirKind::Concat(parts) => parts
+                .iter()
+                .rev()
+                .try_fold(next, |next, part| self.compile(part, next)),
+            HirKind::Alternation(alternatives) => {
+                let mut starts = alternatives
+                    .iter()
+                    .map(|alternative| self.compile(alternative, next))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let mut start = starts.pop().unwrap_or(next);
+                while let Some(alternative) = starts.pop() {
+                    start = self.push(State::Split(alternative, start))?;
+                }
+                Ok(start)
+            }
+            HirKind::Repetition(repetition) => {
+                let mut tail = match repetition.max {
+                    None => {
+                        // The loop state's body leads back to it, so it is patched once compiled
+                        let repeat = self.push(State::Split(next, next))?;
+                        let body = self.compile(&repetition.sub, repeat)?;
+                        self.states[repeat] = State::Split(body, next);
+                        repeat
+                    }
+                    Some(max) => {
+                        let mut tail = next;
+                        for _ in repetition.min..max {
+                            let body = self.compile(&repetition.sub, tail)?;
+                            tail = self.push(State::Split(body, next))?;
+                        }
+                        tail
+                    }
+                };
+                for _ in 0..repetition.min {
+                    tail = self.compile(&repetition.sub, tail)?;
+                }
+                Ok(tail)
+            }
+        }
+    }
+
+    // Adds `state` and everything reachable from it without consuming a character
+    fn close(&self, set: &mut [bool], state: usize) {
+        let mut pending = vec![state];
+        while let Some(state) = pending.pop() {
+            if set[state] {
+                continue;
+            }
+            set[state] = true;
+            if let State::Split(first, second) = self.states[state] {
+                pending.push(first);
+                pending.push(second);
+            }
+        }
+    }
+
+    // Follows the character transitions of `from` into `to`: those accepting `c`, or with
+    // `c` of None all of them, for a substitution or deletion
+    fn step(&self, from: &[bool], c: Option<char>, to: &mut [bool]) {
+        for (state, _) in from.iter().enumerate().filter(|(_, active)| **active) {
+            if let State::Ranges(ranges, next) = &self.states[state]
+                && c.is_none_or(|c| ranges.iter().any(|&(start, end)| start <= c && c <= end))
+            {
+                self.close(to, *next);
+            }
+        }
+    }
+
+    pub(crate) fn is_match(&self, line: &str) -> bool {
+        let count = self.states.len();
+        // active[errors]: the states reachable with at most `errors` errors
+        let mut active = vec![vec![false; count]; self.max_errors + 1];
+        for errors in 0..=self.max_errors {
+            // A match may start anywhere, so the start state is always active
+            self.close(&mut active[errors], self.start);
+            if errors > 0 {
+                let (fewer, current) = active.split_at_mut(errors);
+                merge(current[0].as_mut_slice(), &fewer[errors - 1]);
+                self.step(&fewer[errors - 1], None, &mut current[0]);
+            }
+        }
+        if active[self.max_errors][0] {
+            return true;
+        }
+        for c in line.chars() {
+            let mut next = vec![vec![false; count]; self.max_errors + 1];
+            for errors in 0..=self.max_errors {
+                self.close(&mut next[errors], self.start);
+                self.step(&active[errors], Some(c), &mut next[errors]);
+                if errors > 0 {
+                    let (fewer, current) = next.split_at_mut(errors);
+                    let current = current[0].as_mut_slice();
+                    // Substitution: any character in place of the expected one
+                    self.step(&active[errors - 1], None, current);
+                    // Insertion: `c` is an extra character, so the state stays
+                    merge(current, &active[errors - 1]);
+                    // Deletion: an expected character is missing, so the state advances alone
+                    self.step(&fewer[errors - 1], None, current);
+                    merge(current, &fewer[errors - 1]);
+                }
+            }
+            active = next;
+            // State 0 is the match state, and every set includes the sets with fewer errors
+            if active[self.max_errors][0] {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+fn merge(into: &mut [bool], from: &[bool]) {
+    for (into, from) in into.iter_mut().zip(from) {
+        *into |= *from;
+    }
+}
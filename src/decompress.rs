@@ -0,0 +1,155 @@
+use std::fs::File;
+use std::io::{BufRead, Read};
+use std::path::Path;
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::thread::JoinHandle;
+
+// Every gzip member starts with these two bytes
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+// Gzip is told by its first bytes whatever the file's name, and always decompressed
+pub(crate) fn is_gzip(start: &[u8]) -> bool {
+    start.starts_with(&GZIP_MAGIC)
+}
+
+// Formats decompressed with `--search-zip`, like ripgrep's and `zgrep` do, each by its crate
+// when the build has the feature named after it. Gzip is always decompressed, by `flate2`'s
+// `MultiGzDecoder`.
+pub(crate) enum Compression {
+    Zstd,
+    Xz,
+    Bzip2,
+}
+
+impl Compression {
+    // Told by the magic bytes of the format, or failing that by the file's extension
+    pub(crate) fn detect(path: &Path, start: &[u8]) -> Option<Compression> {
+        if start.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            return Some(Compression::Zstd);
+        }
+        if start.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            return Some(Compression::Xz);
+        }
+        // `BZh` and the block size, from 1 to 9
+        if let [b'B', b'Z', b'h', b'1'..=b'9', ..] = start {
+            return Some(Compression::Bzip2);
+        }
+        match path.extension()?.to_str()? {
+            "zst" | "zstd" => Some(Compression::Zstd),
+            "xz" => Some(Compression::Xz),
+            "bz2" => Some(Compression::Bzip2),
+            _ => None,
+        }
+    }
+
+    // Also the name of the feature that decompresses it
+    fn name(&self) -> &'static str {
+        match self {
+            Compression::Zstd => "zstd",
+            Compression::Xz => "xz",
+            Compression::Bzip2 => "bzip2",
+        }
+    }
+
+    // Decompresses `input` as it is read. Concatenated frames, streams or members are read one
+    // after the other, as the tools do, and damaged input is an error rather than a shorter file.
+    pub(crate) fn open<'a>(&self, input: impl BufRead + 'a) -> std::io::Result<Box<dyn Read + 'a>> {
+        match self {
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(Box::new(zstd::stream::read::Decoder::with_buffer(input)?)),
+            #[cfg(feature = "xz")]
+            Compression::Xz => Ok(Box::new(xz2::bufread::XzDecoder::new_multi_decoder(input))),
+            #[cfg(feature = "bzip2")]
+            Compression::Bzip2 => Ok(Box::new(bzip2::bufread::MultiBzDecoder::new(input))),
+            #[allow(unreachable_patterns)]
+            _ => {
+                drop(input);
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    format!(
+                        "{} decompression isn't included in this build, rebuild with `--features {}`",
+                        self.name(),
+                        self.name()
+                    ),
+                ))
+            }
+        }
+    }
+}
+
+// Runs a `--pre` command on a file, like ripgrep's: it is given the file's path, and the file
+// on its stdin, and what it writes is searched in the file's place
+pub(crate) fn preprocess(command: &str, path: &Path) -> std::io::Result<Preprocessed> {
+    let mut child = Command::new(command)
+        .arg(path)
+        .stdin(File::open(path)?)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| {
+            std::io::Error::new(
+                err.kind(),
+                format!("preprocessor {command} couldn't be run: {err}"),
+            )
+        })?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    // Read as it is written, so a command writing much of it never waits on a full pipe while
+    // its output is waited for
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+    let stderr = std::thread::spawn(move || {
+        let mut message = String::new();
+        let _ = stderr.read_to_string(&mut message);
+        message
+    });
+    Ok(Preprocessed {
+        child,
+        stdout,
+        stderr: Some(stderr),
+        command: command.to_string(),
+        failure: None,
+    })
+}
+
+// The output of a preprocessor. It ends with an error if the command fails, so a failure isn't
+// mistaken for a shorter file.
+pub(crate) struct Preprocessed {
+    child: Child,
+    stdout: ChildStdout,
+    // What it writes to stderr, once it is done
+    stderr: Option<JoinHandle<String>>,
+    command: String,
+    // Why the command failed, for every read after the one that found out
+    failure: Option<String>,
+}
+
+impl Read for Preprocessed {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if let Some(failure) = &self.failure {
+            return Err(std::io::Error::other(failure.clone()));
+        }
+        let read = self.stdout.read(buf)?;
+        if read == 0 && !buf.is_empty() {
+            let status = self.child.wait()?;
+            let message = match self.stderr.take() {
+                Some(stderr) => stderr.join().unwrap_or_default(),
+                None => String::new(),
+            };
+            if !status.success() {
+                let failure = format!("{} failed: {}", self.command, message.trim());
+                self.failure = Some(failure.clone());
+                return Err(std::io::Error::other(failure));
+            }
+        }
+        Ok(read)
+    }
+}
+
+impl Drop for Preprocessed {
+    // A search that stops early leaves the rest of the output unread
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use std::borrow::Cow;
+use std::io::{BufRead, Read};
+
+// UTF-8 text may start with a BOM too, as Windows editors write it
+pub(crate) const UTF8_BOM: [u8; 3] = [0xef, 0xbb, 0xbf];
+
+// How much of the start of the input is looked at to tell UTF-16 without a BOM
+const SNIFF_LENGTH: usize = 4096;
+
+// Encodings that are transcoded to UTF-8 before matching
+#[derive(Clone, Copy)]
+pub(crate) enum Decoding {
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+}
+
+// UTF-16 told by its BOM, or without one by the NUL bytes of the high halves of ASCII
+// characters, which fall on every other byte. Anything else is taken as UTF-8, with lines that
+// aren't read as Latin-1 by `Fallback::Latin1`.
+pub(crate) fn sniff(start: &[u8]) -> Option<Decoding> {
+    if start.starts_with(&[0xff, 0xfe]) {
+        return Some(Decoding::Utf16Le);
+    }
+    if start.starts_with(&[0xfe, 0xff]) {
+        return Some(Decoding::Utf16Be);
+    }
+    let start = &start[..start.len().min(SNIFF_LENGTH) & !1];
+    if start.len() < 4 {
+        return None;
+    }
+    let pairs = start.len() / 2;
+    let zeros = |half: usize| start.chunks_exact(2).filter(|pair| pair[half] == 0).count();
+    let (low, high) = (zeros(0), zeros(1));
+    // Nine in ten characters on one side, hardly any on the other
+    let mostly = |count: usize| count * 10 >= pairs * 9;
+    let hardly = |count: usize| count * 10 <= pairs;
+    match (low, high) {
+        (low, high) if mostly(high) && hardly(low) => Some(Decoding::Utf16Le),
+        (low, high) if mostly(low) && hardly(high) => Some(Decoding::Utf16Be),
+        _ => None,
+    }
+}
+
+// How long the BOM at the start of the input is, which isn't part of the first line
+pub(crate) fn bom_len(decoding: Option<Decoding>, start: &[u8]) -> usize {
+    match decoding {
+        None if start.starts_with(&UTF8_BOM) => UTF8_BOM.len(),
+        Some(Decoding::Utf16Le) if start.starts_with(&[0xff, 0xfe]) => 2,
+        Some(Decoding::Utf16Be) if start.starts_with(&[0xfe, 0xff]) => 2,
+        _ => 0,
+    }
+}
+
+// How many bytes of the input `decoding` turned into `text`, so offsets into transcoded text can
+// be given as offsets into the input. What couldn't be decoded counts as a whole character.
+pub(crate) fn source_len(decoding: Decoding, text: &str) -> usize {
+    match decoding {
+        Decoding::Latin1 => text.chars().count(),
+        Decoding::Utf16Le | Decoding::Utf16Be => text.chars().map(|c| c.len_utf16() * 2).sum(),
+    }
+}
+
+// What a line that isn't valid UTF-8 is read as
+#[derive(Clone, Copy)]
+pub(crate) enum Fallback {
+    // Nothing: the read fails
+    Fail,
+    // UTF-8 with what isn't replaced by U+FFFD
+    Replace,
+    // Latin-1, which any byte is, as legacy 8-bit text most likely is
+    Latin1,
+}
+
+impl Fallback {
+    // The line as text, or None when it fails the read
+    pub(crate) fn decode(self, line: &[u8]) -> Option<Cow<'_, str>> {
+        if let Ok(line) = std::str::from_utf8(line) {
+            return Some(Cow::Borrowed(line));
+        }
+        match self {
+            Fallback::Fail => None,
+            Fallback::Replace => Some(String::from_utf8_lossy(line)),
+            Fallback::Latin1 => Some(Cow::Owned(
+                line.iter().map(|byte| char::from(*byte)).collect(),
+            )),
+        }
+    }
+}
+
+// Reads input in another encoding as UTF-8. Without a decoding it passes the input through as
+// it is. A BOM at the start is left out, and what can't be decoded becomes U+FFFD.
+pub(crate) struct Transcoder<R> {
+    input: R,
+    decoding: Option<Decoding>,
+    // Decoded output not read yet
+    output: Vec<u8>,
+    position: usize,
+    started: bool,
+    // The unpaired half of a surrogate pair, or a lone byte, left at the end of a read
+    pending: Vec<u8>,
+}
+
+impl<R: BufRead> Transcoder<R> {
+    pub(crate) fn new(input: R, decoding: Option<Decoding>) -> Transcoder<R> {
+        Transcoder {
+            input,
+            decoding,
+            output: Vec::new(),
+            position: 0,
+            started: false,
+            pending: Vec::new(),
+        }
+    }
+
+    // Decodes the next block of input, which leaves the output empty at the end of it
+    fn decode_more(&mut self, decoding: Decoding) -> std::io::Result<()> {
+        self.output.clear();
+        self.position = 0;
+        while self.output.is_empty() {
+            let block = self.input.fill_buf()?;
+            if block.is_empty() {
+                // Half a character at the end of the input
+                if !self.pending.is_empty() {
+                    self.pending.clear();
+                    self.output.extend_from_slice("\u{fffd}".as_bytes());
+                }
+                return Ok(());
+            }
+            let consumed = block.len();
+            let mut bytes = std::mem::take(&mut self.pending);
+            bytes.extend_from_slice(block);
+            self.input.consume(consumed);
+            let mut text = String::new();
+            match decoding {
+                Decoding::Latin1 => text.extend(bytes.iter().map(|byte| char::from(*byte))),
+                Decoding::Utf16Le | Decoding::Utf16Be => {
+                    let units: Vec<u16> = bytes
+                        .chunks_exact(2)
+                        .map(|pair| match decoding {
+                            Decoding::Utf16Le => u16::from_le_bytes([pair[0], pair[1]]),
+                            _ => u16::from_be_bytes([pair[0], pair[1]]),
+                        })
+                        .collect();
+                    // A character may be split between this block and the next
+                    let mut whole = units.len();
+                    if units
+                        .last()
+                        .is_some_and(|unit| (0xd800..0xdc00).contains(unit))
+                    {
+                        whole -= 1;
+                    }
+                    text.extend(
+                        char::decode_utf16(units[..whole].iter().copied())
+                            .map(|decoded| decoded.unwrap_or(char::REPLACEMENT_CHARACTER)),
+                    );
+                    self.pending = bytes[whole * 2..].to_vec();
+                }
+            }
+            let text = match self.started {
+                true => text.as_str(),
+                false => text.strip_prefix('\u{feff}').unwrap_or(&text),
+            };
+            self.started = true;
+            self.output.extend_from_slice(text.as_bytes());
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for Transcoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let available = self.fill_buf()?;
+        let read = available.len().min(buf.len());
+        buf[..read].copy_from_slice(&available[..read]);
+        self.consume(read);
+        Ok(read)
+    }
+}
+
+impl<R: BufRead> BufRead for Transcoder<R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        let Some(decoding) = self.decoding else {
+            return self.input.fill_buf();
+        };
+        if self.position == self.output.len() {
+            self.decode_more(decoding)?;
+        }
+        Ok(&self.output[self.position..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        match self.decoding {
+            Some(_) => self.position += amount,
+            None => self.input.consume(amount),
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use crate::{SearchError, fold_case};
+
+// A boolean combination of substring terms, e.g. `(error AND timeout) NOT retry`.
+// `a NOT b` reads as `a AND NOT b`, and NOT also works as a prefix: `NOT retry`.
+// AND binds tighter than OR. Terms with spaces or operator names can be quoted: `"NOT FOUND"`.
+pub(crate) enum Expr {
+    Term(String),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Term(String),
+}
+
+impl Expr {
+    // Terms are case folded up front when matching case-insensitively, so evaluation only has
+    // to fold the line
+    pub(crate) fn parse(source: &str, case_insensitive: bool) -> Result<Expr, SearchError> {
+        let invalid = |reason: &str| {
+            SearchError::InitializationError(format!("expression {source} is invalid: {reason}"))
+        };
+        let mut tokens = tokenize(source, case_insensitive)
+            .map_err(|reason| invalid(&reason))?
+            .into_iter()
+            .peekable();
+        let expr = parse_or(&mut tokens).map_err(|reason| invalid(&reason))?;
+        match tokens.next() {
+            None => Ok(expr),
+            Some(Token::Close) => Err(invalid("unbalanced ')'")),
+            Some(_) => Err(invalid("expected AND, OR or NOT between terms")),
+        }
+    }
+
+    pub(crate) fn is_match(&self, line: &str) -> bool {
+        match self {
+            Expr::Term(term) => line.contains(term.as_str()),
+            Expr::Not(expr) => !expr.is_match(line),
+            Expr::And(left, right) => left.is_match(line) && right.is_match(line),
+            Expr::Or(left, right) => left.is_match(line) || right.is_match(line),
+        }
+    }
+}
+
+type Tokens = std::iter::Peekable<std::vec::IntoIter<Token>>;
+
+fn tokenize(source: &str, case_insensitive: bool) -> Result<Vec<Token>, String> {
+    let term = |text: String| match case_insensitive {
+        true => Token::Term(fold_case(&text)),
+        false => Token::Term(text),
+    };
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '"' => {
+                chars.next();
+                let quoted: String = chars.by_ref().take_while(|&c| c != '"').collect();
+                if quoted.is_empty() {
+                    return Err("empty quoted term".to_string());
+                }
+                tokens.push(term(quoted));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => term(word),
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_or(tokens: &mut Tokens) -> Result<Expr, String> {
+    let mut expr = parse_and(tokens)?;
+    while let Some(Token::Or) = tokens.peek() {
+        tokens.next();
+        expr = Expr::Or(Box::new(expr), Box::new(parse_and(tokens)?));
+    }
+    Ok(expr)
+}
+
+fn parse_and(tokens: &mut Tokens) -> Result<Expr, String> {
+    let mut expr = parse_unary(tokens)?;
+    loop {
+        match tokens.peek() {
+            Some(Token::And) => {
+                tokens.next();
+                expr = Expr::And(Box::new(expr), Box::new(parse_unary(tokens)?));
+            }
+            // Infix NOT: `a NOT b` is `a AND NOT b`
+            Some(Token::Not) => {
+                tokens.next();
+                let negated = Expr::Not(Box::new(parse_unary(tokens)?));
+                expr = Expr::And(Box::new(expr), Box::new(negated));
+            }
+            _ => return Ok(expr),
+        }
+    }
+}
+
+fn parse_unary(tokens: &mut Tokens) -> Result<Expr, String> {
+    match tokens.next() {
+        Some(Token::Not) => Ok(Expr::Not(Box::new(parse_unary(tokens)?))),
+        Some(Token::Open) => {
+            let expr = parse_or(tokens)?;
+            match tokens.next() {
+                Some(Token::Close) => Ok(expr),
+                _ => Err("unbalanced '('".to_string()),
+            }
+        }
+        Some(Token::Term(term)) => Ok(Expr::Term(term)),
+        Some(Token::Close) => Err("expected a term before ')'".to_string()),
+        Some(Token::And | Token::Or) => Err("expected a term before AND or OR".to_string()),
+        None => Err("expected a term".to_string()),
+    }
+}
@@ -0,0 +1,221 @@
+use std::fs::Metadata;
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::gitignore::{self, Rule};
+use crate::{Config, SearchError};
+
+// The file types `--type` knows without `--type-add`, by the names of their files
+const FILE_TYPES: &[(&str, &[&str])] = &[
+    ("c", &["*.c", "*.h"]),
+    (
+        "cpp",
+        &["*.cpp", "*.cc", "*.cxx", "*.hpp", "*.hh", "*.hxx", "*.h"],
+    ),
+    ("cs", &["*.cs"]),
+    ("css", &["*.css", "*.scss", "*.sass", "*.less"]),
+    ("go", &["*.go"]),
+    ("html", &["*.html", "*.htm"]),
+    ("java", &["*.java"]),
+    ("js", &["*.js", "*.mjs", "*.cjs", "*.jsx"]),
+    ("json", &["*.json"]),
+    ("kotlin", &["*.kt", "*.kts"]),
+    ("log", &["*.log"]),
+    ("lua", &["*.lua"]),
+    ("make", &["Makefile", "makefile", "GNUmakefile", "*.mk"]),
+    ("md", &["*.md", "*.markdown"]),
+    ("php", &["*.php"]),
+    ("py", &["*.py", "*.pyi"]),
+    ("rb", &["*.rb", "Gemfile", "Rakefile"]),
+    ("rust", &["*.rs"]),
+    ("sh", &["*.sh", "*.bash", "*.zsh"]),
+    ("sql", &["*.sql"]),
+    ("swift", &["*.swift"]),
+    ("toml", &["*.toml"]),
+    ("ts", &["*.ts", "*.tsx", "*.mts", "*.cts"]),
+    ("txt", &["*.txt"]),
+    ("xml", &["*.xml"]),
+    ("yaml", &["*.yaml", "*.yml"]),
+];
+
+// The file-selection filters of a directory search, which decide which of the files found in
+// it are searched. They see paths relative to the directory searched.
+pub(crate) struct FileFilter {
+    // The `--glob` patterns, in the order given, written like ignore rules. Unlike those, a glob
+    // selects what it matches, and one starting with `!` leaves it out.
+    globs: Vec<Rule>,
+    // With any glob that selects, only the files one of them matches are searched
+    selecting: bool,
+    // The globs of the `--type` types, of which a file must match one when there are any, and
+    // those of the `--type-not` types, of which it must match none
+    types: Vec<Rule>,
+    types_not: Vec<Rule>,
+    // The `--pre-glob` patterns, which pick the files `--pre` runs on rather than those searched
+    pre_globs: Vec<Rule>,
+    max_filesize: Option<u64>,
+    newer_than: Option<SystemTime>,
+    older_than: Option<SystemTime>,
+}
+
+impl FileFilter {
+    pub(crate) fn new(config: &Config) -> Result<FileFilter, SearchError> {
+        let globs = config
+            .globs
+            .iter()
+            .map(|glob| parse_glob(glob))
+            .collect::<Result<Vec<_>, _>>()?;
+        let selecting = globs.iter().any(|glob| !glob.negated);
+        // `--type-add name:glob` defines a type, or adds to one
+        let mut definitions: Vec<(String, String)> = FILE_TYPES
+            .iter()
+            .flat_map(|(name, globs)| {
+                globs
+                    .iter()
+                    .map(|glob| (name.to_string(), glob.to_string()))
+            })
+            .collect();
+        for definition in config.type_definitions.iter() {
+            match definition.split_once(':') {
+                Some((name, glob)) if !name.is_empty() && !glob.is_empty() => {
+                    definitions.push((name.to_string(), glob.to_string()))
+                }
+                _ => {
+                    return Err(SearchError::InitializationError(format!(
+                        "type definition {definition} is invalid, it should be NAME:GLOB"
+                    )));
+                }
+            }
+        }
+        let type_globs = |names: &[String]| -> Result<Vec<Rule>, SearchError> {
+            let mut rules = Vec::new();
+            for name in names {
+                let globs: Vec<&String> = definitions
+                    .iter()
+                    .filter(|(defined, _)| defined == name)
+                    .map(|(_, glob)| glob)
+                    .collect();
+                if globs.is_empty() {
+                    return Err(SearchError::InitializationError(format!(
+                        "file type {name} is invalid"
+                    )));
+                }
+                for glob in globs {
+                    rules.push(parse_glob(glob)?);
+                }
+            }
+            Ok(rules)
+        };
+        Ok(FileFilter {
+            globs,
+            selecting,
+            types: type_globs(&config.types)?,
+            types_not: type_globs(&config.types_not)?,
+            pre_globs: config
+                .pre_globs
+                .iter()
+                .map(|glob| parse_glob(glob))
+                .collect::<Result<Vec<_>, _>>()?,
+            max_filesize: config.max_filesize,
+            newer_than: config.newer_than,
+            older_than: config.older_than,
+        })
+    }
+
+    // Whether `--pre` runs on a file: on every one without `--pre-glob`, and otherwise on those
+    // the last matching glob selects, or those none matches, if the globs only leave files out
+    pub(crate) fn preprocesses(&self, path: &Path) -> bool {
+        let path = gitignore::slashed(path);
+        self.pre_globs
+            .iter()
+            .rev()
+            .find(|glob| !glob.dir_only && glob.regex.is_match(&path))
+            .map_or_else(
+                || self.pre_globs.iter().all(|glob| glob.negated),
+                |glob| !glob.negated,
+            )
+    }
+
+    // Whether a directory is left out, with everything in it, by a `!` glob matching it
+    pub(crate) fn excludes_dir(&self, relative: &Path) -> bool {
+        self.decision(relative, true) == Some(false)
+    }
+
+    // Whether the filters look at more than a file's path
+    pub(crate) fn needs_metadata(&self) -> bool {
+        self.max_filesize.is_some() || self.newer_than.is_some() || self.older_than.is_some()
+    }
+
+    // Whether a file is searched, by its path and by its `metadata`, when that is known
+    pub(crate) fn selects_file(&self, relative: &Path, metadata: Option<&Metadata>) -> bool {
+        let size = metadata.map(|metadata| metadata.len());
+        let modified = metadata.and_then(|metadata| metadata.modified().ok());
+        self.selects(relative, size, modified)
+    }
+
+    // Like `selects_file`, for what is known of a file that isn't on disk, like a stored object
+    pub(crate) fn selects(
+        &self,
+        relative: &Path,
+        size: Option<u64>,
+        modified: Option<SystemTime>,
+    ) -> bool {
+        let small_enough = || match (self.max_filesize, size) {
+            (Some(max_filesize), Some(size)) => size <= max_filesize,
+            _ => true,
+        };
+        let recent_enough = || match (self.newer_than, modified) {
+            (Some(newer_than), Some(modified)) => modified > newer_than,
+            _ => true,
+        };
+        let old_enough = || match (self.older_than, modified) {
+            (Some(older_than), Some(modified)) => modified < older_than,
+            _ => true,
+        };
+        self.selects_path(relative) && small_enough() && recent_enough() && old_enough()
+    }
+
+    // By the last glob matching the path, or when none does, unless there are globs that
+    // select, by its type. A file in a directory that is left out is left out too.
+    fn selects_path(&self, relative: &Path) -> bool {
+        let in_excluded_dir = relative
+            .ancestors()
+            .skip(1)
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .any(|dir| self.excludes_dir(dir));
+        if in_excluded_dir {
+            return false;
+        }
+        if let Some(decision) = self.decision(relative, false) {
+            return decision;
+        }
+        if self.selecting {
+            return false;
+        }
+        let relative = gitignore::slashed(relative);
+        let of_type = |rules: &[Rule]| rules.iter().any(|rule| rule.regex.is_match(&relative));
+        (self.types.is_empty() || of_type(&self.types)) && !of_type(&self.types_not)
+    }
+
+    // What the last glob matching a path decides, if one does
+    fn decision(&self, relative: &Path, is_dir: bool) -> Option<bool> {
+        if self.globs.is_empty() {
+            return None;
+        }
+        let relative = gitignore::slashed(relative);
+        self.globs
+            .iter()
+            .rev()
+            .find(|glob| (is_dir || !glob.dir_only) && glob.regex.is_match(&relative))
+            .map(|glob| !glob.negated)
+    }
+}
+
+fn parse_glob(glob: &str) -> Result<Rule, SearchError> {
+    // A leading `#` is part of a name here, not a comment
+    let line = match glob.starts_with('#') {
+        true => format!("\\{glob}"),
+        false => glob.to_string(),
+    };
+    gitignore::parse_rule(&line)
+        .ok_or_else(|| SearchError::InitializationError(format!("glob {glob} is invalid")))
+}
@@ -0,0 +1,111 @@
+use std::fs::{File, Metadata};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+// How long to wait at the end of the file before looking for more
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// Lines appended to a followed file
+pub(crate) struct Appended {
+    pub(crate) lines: Vec<u8>,
+    // Whether they start the file over, after it was truncated or replaced
+    pub(crate) from_start: bool,
+}
+
+// Reads a file as lines are appended to it, like `tail -F`. A file that is truncated is read
+// again from its start, and so is the new file when the path is given to another one, as log
+// rotation does. One that is removed is waited for.
+pub(crate) struct Follower {
+    path: PathBuf,
+    file: File,
+    // How much of the current file has been read
+    read: u64,
+    // The start of a line that isn't complete yet
+    partial: Vec<u8>,
+    from_start: bool,
+}
+
+impl Follower {
+    pub(crate) fn open(path: &Path) -> std::io::Result<Follower> {
+        Ok(Follower {
+            path: path.to_path_buf(),
+            file: File::open(path)?,
+            read: 0,
+            partial: Vec::new(),
+            from_start: true,
+        })
+    }
+
+    // Waits for the next complete lines, or for `stop`, which gives nothing
+    pub(crate) fn next_lines(&mut self, stop: &AtomicBool) -> std::io::Result<Option<Appended>> {
+        loop {
+            if stop.load(Ordering::SeqCst) {
+                return Ok(None);
+            }
+            let mut appended = Vec::new();
+            self.read += (&self.file).read_to_end(&mut appended)? as u64;
+            if !appended.is_empty() {
+                self.partial.extend_from_slice(&appended);
+                if let Some(end) = memchr::memrchr(b'\n', &self.partial) {
+                    let rest = self.partial.split_off(end + 1);
+                    let lines = std::mem::replace(&mut self.partial, rest);
+                    return Ok(Some(self.appended(lines)));
+                }
+                continue;
+            }
+            // At the end of the file, which may have been replaced or cut short meanwhile
+            let current = self.file.metadata()?;
+            match std::fs::metadata(&self.path) {
+                Ok(found) if !same_file(&found, &current) => {
+                    // The last line of the file that was replaced is complete now
+                    if !self.partial.is_empty() {
+                        let lines = std::mem::take(&mut self.partial);
+                        return Ok(Some(self.appended(lines)));
+                    }
+                    match File::open(&self.path) {
+                        Ok(file) => self.restart(file)?,
+                        // Gone again, or not readable yet
+                        Err(_) => std::thread::sleep(POLL_INTERVAL),
+                    }
+                }
+                Ok(found) if found.len() < self.read => {
+                    let file = self.file.try_clone()?;
+                    self.restart(file)?;
+                }
+                _ => std::thread::sleep(POLL_INTERVAL),
+            }
+        }
+    }
+
+    fn appended(&mut self, lines: Vec<u8>) -> Appended {
+        Appended {
+            lines,
+            from_start: std::mem::take(&mut self.from_start),
+        }
+    }
+
+    fn restart(&mut self, mut file: File) -> std::io::Result<()> {
+        file.seek(SeekFrom::Start(0))?;
+        self.file = file;
+        self.read = 0;
+        self.partial.clear();
+        self.from_start = true;
+        Ok(())
+    }
+}
+
+// Where files can't be told apart by their device and inode, a replaced one is only noticed
+// when it is shorter
+#[cfg(unix)]
+fn same_file(found: &Metadata, current: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    (found.dev(), found.ino()) == (current.dev(), current.ino())
+}
+
+#[cfg(not(unix))]
+fn same_file(_found: &Metadata, _current: &Metadata) -> bool {
+    true
+}
@@ -0,0 +1,218 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use regex::Regex;
+
+// The files each directory may have ignore rules in. Rules in `.ignore` come later, so they can
+// override those of `.gitignore`, and those of `.searchignore`, which is this tool's own, last.
+const IGNORE_FILES: [&str; 3] = [".gitignore", ".ignore", ".searchignore"];
+
+// One line of an ignore file
+pub(crate) struct Rule {
+    // Matches paths relative to the directory of the ignore file
+    pub(crate) regex: Regex,
+    // `!pattern`, which brings back what an earlier rule ignored
+    pub(crate) negated: bool,
+    // `pattern/`, which only matches directories
+    pub(crate) dir_only: bool,
+}
+
+// The ignore rules that apply in a directory: those of its own ignore files, then those of the
+// directories above it. The rules of deeper directories take precedence.
+pub(crate) struct Ignores {
+    // Where the rules apply, as the walk reaches it
+    dir: PathBuf,
+    // For the directories above the root of the search, the root's path below them
+    prefix: PathBuf,
+    rules: Vec<Rule>,
+    parent: Option<Arc<Ignores>>,
+}
+
+impl Ignores {
+    // The rules for the root of a search. Those of the directories above it count too, up to
+    // the root of the git repository it is in, and below all of them those of the `global`
+    // ignore file, which apply to paths relative to the root, like git's `core.excludesFile`.
+    pub(crate) fn for_root(root: &Path, global: Option<&Path>) -> Arc<Ignores> {
+        let rules = global
+            .and_then(|global| std::fs::read_to_string(global).ok())
+            .map(|source| source.lines().filter_map(parse_rule).collect())
+            .unwrap_or_default();
+        let mut ignores = Arc::new(Ignores {
+            dir: root.to_path_buf(),
+            prefix: PathBuf::new(),
+            rules,
+            parent: None,
+        });
+        let canonical = std::fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+        if let Some(repository) = canonical.ancestors().find(|dir| dir.join(".git").exists()) {
+            let above: Vec<&Path> = canonical
+                .ancestors()
+                .skip(1)
+                .take_while(|dir| dir.starts_with(repository))
+                .collect();
+            for dir in above.into_iter().rev() {
+                let rules = read_rules(dir);
+                if !rules.is_empty() {
+                    ignores = Arc::new(Ignores {
+                        dir: root.to_path_buf(),
+                        prefix: canonical
+                            .strip_prefix(dir)
+                            .unwrap_or(&canonical)
+                            .to_path_buf(),
+                        rules,
+                        parent: Some(ignores),
+                    });
+                }
+            }
+        }
+        ignores.enter(root)
+    }
+
+    // The rules for a directory below this one. A directory without ignore files shares the
+    // rules of its parent.
+    pub(crate) fn enter(self: &Arc<Ignores>, dir: &Path) -> Arc<Ignores> {
+        let rules = read_rules(dir);
+        if rules.is_empty() {
+            return Arc::clone(self);
+        }
+        Arc::new(Ignores {
+            dir: dir.to_path_buf(),
+            prefix: PathBuf::new(),
+            rules,
+            parent: Some(Arc::clone(self)),
+        })
+    }
+
+    // Whether a file or directory found in the walk is ignored: by the last rule matching it in
+    // the deepest directory that has one, or as a `.git` directory
+    pub(crate) fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        // What git keeps of its own is never part of what it tracks
+        if is_dir && path.file_name().is_some_and(|name| name == ".git") {
+            return true;
+        }
+        let mut ignores = Some(self);
+        while let Some(current) = ignores {
+            if let Ok(relative) = path.strip_prefix(&current.dir) {
+                let relative = slashed(&current.prefix.join(relative));
+                let decision = current
+                    .rules
+                    .iter()
+                    .rev()
+                    .find(|rule| (is_dir || !rule.dir_only) && rule.regex.is_match(&relative));
+                if let Some(rule) = decision {
+                    return !rule.negated;
+                }
+            }
+            ignores = current.parent.as_deref();
+        }
+        false
+    }
+}
+
+// Patterns are written with `/` between components
+pub(crate) fn slashed(path: &Path) -> String {
+    let components: Vec<_> = path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    components.join("/")
+}
+
+fn read_rules(dir: &Path) -> Vec<Rule> {
+    IGNORE_FILES
+        .iter()
+        .filter_map(|name| std::fs::read_to_string(dir.join(name)).ok())
+        .flat_map(|source| source.lines().filter_map(parse_rule).collect::<Vec<_>>())
+        .collect()
+}
+
+pub(crate) fn parse_rule(line: &str) -> Option<Rule> {
+    // Trailing spaces don't count, unless escaped
+    let mut pattern = line.trim_end_matches(' ');
+    if pattern.ends_with('\\') && line.len() > pattern.len() {
+        pattern = &line[..pattern.len() + 1];
+    }
+    if pattern.is_empty() || pattern.starts_with('#') {
+        return None;
+    }
+    let (negated, pattern) = match pattern.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, pattern),
+    };
+    let (dir_only, pattern) = match pattern.strip_suffix('/') {
+        Some(rest) => (true, rest),
+        None => (false, pattern),
+    };
+    // A pattern with a slash before its end is relative to the ignore file's directory; one
+    // without matches a name at any depth
+    let anchored = pattern.contains('/');
+    let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+    if pattern.is_empty() {
+        return None;
+    }
+    let prefix = match anchored {
+        true => "^",
+        false => "^(?:.*/)?",
+    };
+    let regex = Regex::new(&format!("{prefix}{}$", translate(pattern))).ok()?;
+    Some(Rule {
+        regex,
+        negated,
+        dir_only,
+    })
+}
+
+// A glob as a regex: `*` and `?` don't cross a `/`, while `**` does, as a whole component
+fn translate(pattern: &str) -> String {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut regex = String::new();
+    let mut at = 0;
+    while at < chars.len() {
+        let rest = &chars[at..];
+        match rest {
+            ['*', '*', '/', ..] if at == 0 || chars[at - 1] == '/' => {
+                regex.push_str("(?:.*/)?");
+                at += 3;
+            }
+            ['*', '*'] if at > 0 && chars[at - 1] == '/' => {
+                regex.push_str(".*");
+                at += 2;
+            }
+            ['*', ..] => {
+                regex.push_str("[^/]*");
+                at += 1;
+            }
+            ['?', ..] => {
+                regex.push_str("[^/]");
+                at += 1;
+            }
+            ['[', ..] => match rest.iter().skip(2).position(|c| *c == ']') {
+                Some(end) => {
+                    let class: String = rest[1..end + 2].iter().collect();
+                    let class = match class.strip_prefix('!') {
+                        Some(negated) => format!("^{negated}"),
+                        None => class,
+                    };
+                    regex.push('[');
+                    regex.push_str(&class.replace('\\', "\\\\").replace('[', "\\["));
+                    regex.push(']');
+                    at += end + 3;
+                }
+                None => {
+                    regex.push_str("\\[");
+                    at += 1;
+                }
+            },
+            ['\\', escaped, ..] => {
+                regex.push_str(&regex::escape(&escaped.to_string()));
+                at += 2;
+            }
+            [c, ..] => {
+                regex.push_str(&regex::escape(&c.to_string()));
+                at += 1;
+            }
+            [] => break,
+        }
+    }
+    regex
+}
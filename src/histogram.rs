@@ -0,0 +1,164 @@
+use crate::template::civil_from_days;
+use crate::{Bucket, SearchError};
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+// A strftime-like pattern for the timestamps of log lines, e.g. `%Y-%m-%d %H:%M:%S`, found
+// anywhere in a line. A space in it also matches the `T` of ISO 8601 timestamps.
+pub(crate) struct TimestampFormat {
+    parts: Vec<Part>,
+}
+
+enum Part {
+    Literal(char),
+    // Four digits
+    Year,
+    // Two digits each
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+    // `Jan` to `Dec`
+    MonthName,
+}
+
+// The fields a timestamp was read into, as far as the format has them
+#[derive(Default)]
+struct Fields {
+    year: Option<i64>,
+    month: Option<i64>,
+    day: Option<i64>,
+    hour: Option<i64>,
+    minute: Option<i64>,
+}
+
+impl TimestampFormat {
+    pub(crate) fn parse(source: &str, bucket: &Bucket) -> Result<TimestampFormat, SearchError> {
+        let invalid = |reason: &str| {
+            SearchError::InitializationError(format!(
+                "timestamp format {source} is invalid: {reason}"
+            ))
+        };
+        let mut parts = Vec::new();
+        let mut chars = source.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                parts.push(Part::Literal(c));
+                continue;
+            }
+            parts.push(match chars.next() {
+                Some('Y') => Part::Year,
+                Some('m') => Part::Month,
+                Some('b') => Part::MonthName,
+                Some('d') => Part::Day,
+                Some('H') => Part::Hour,
+                Some('M') => Part::Minute,
+                Some('S') => Part::Second,
+                Some('%') => Part::Literal('%'),
+                Some(other) => return Err(invalid(&format!("unknown field %{other}"))),
+                None => return Err(invalid("it ends with a lone '%'")),
+            });
+        }
+        let has = |wanted: fn(&Part) -> bool| parts.iter().any(wanted);
+        let dated = has(|part| matches!(part, Part::Year))
+            && has(|part| matches!(part, Part::Month | Part::MonthName))
+            && has(|part| matches!(part, Part::Day))
+            && has(|part| matches!(part, Part::Hour));
+        match bucket {
+            Bucket::Hour if !dated => Err(invalid("counting by the hour needs %Y, %m, %d and %H")),
+            Bucket::Minute if !dated || !has(|part| matches!(part, Part::Minute)) => Err(invalid(
+                "counting by the minute needs %Y, %m, %d, %H and %M",
+            )),
+            _ => Ok(TimestampFormat { parts }),
+        }
+    }
+
+    // The bucket of the first timestamp in the line, as a count of buckets since the Unix epoch
+    pub(crate) fn bucket_of(&self, line: &str, bucket: &Bucket) -> Option<i64> {
+        let fields = line
+            .char_indices()
+            .find_map(|(start, _)| self.read_at(&line[start..]))?;
+        let days = days_from_civil(fields.year?, fields.month?, fields.day?);
+        let hours = days * 24 + fields.hour?;
+        match bucket {
+            Bucket::Hour => Some(hours),
+            Bucket::Minute => Some(hours * 60 + fields.minute?),
+        }
+    }
+
+    fn read_at(&self, mut text: &str) -> Option<Fields> {
+        let mut fields = Fields::default();
+        for part in self.parts.iter() {
+            let (value, rest) = match part {
+                Part::Literal(' ') if text.starts_with(['T', ' ']) => (None, &text[1..]),
+                Part::Literal(c) => (None, text.strip_prefix(*c)?),
+                Part::Year => (Some(digits(text, 4, 0, 9999)?), &text[4..]),
+                Part::Month => (Some(digits(text, 2, 1, 12)?), &text[2..]),
+                // Days may be padded with a space, as syslog does
+                Part::Day => match text.strip_prefix(' ') {
+                    Some(rest) => (Some(digits(rest, 1, 1, 9)?), &rest[1..]),
+                    None => (Some(digits(text, 2, 1, 31)?), &text[2..]),
+                },
+                Part::Hour => (Some(digits(text, 2, 0, 23)?), &text[2..]),
+                Part::Minute => (Some(digits(text, 2, 0, 59)?), &text[2..]),
+                // Up to 60 for a leap second
+                Part::Second => (Some(digits(text, 2, 0, 60)?), &text[2..]),
+                Part::MonthName => {
+                    let month = MONTHS.iter().position(|name| text.starts_with(name))?;
+                    (Some(month as i64 + 1), &text[3..])
+                }
+            };
+            match (part, value) {
+                (Part::Year, year) => fields.year = year,
+                (Part::Month | Part::MonthName, month) => fields.month = month,
+                (Part::Day, day) => fields.day = day,
+                (Part::Hour, hour) => fields.hour = hour,
+                (Part::Minute, minute) => fields.minute = minute,
+                _ => (),
+            }
+            text = rest;
+        }
+        Some(fields)
+    }
+}
+
+// How a bucket is printed: `2024-03-01 12:30` for a minute, `2024-03-01 12:00` for an hour
+pub(crate) fn label(value: i64, bucket: &Bucket) -> String {
+    let minutes = match bucket {
+        Bucket::Hour => value * 60,
+        Bucket::Minute => value,
+    };
+    let (year, month, day) = civil_from_days(minutes.div_euclid(24 * 60));
+    let minute_of_day = minutes.rem_euclid(24 * 60);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}",
+        minute_of_day / 60,
+        minute_of_day % 60
+    )
+}
+
+// The value of the `count` ASCII digits `text` starts with, if it is within `min..=max`
+fn digits(text: &str, count: usize, min: i64, max: i64) -> Option<i64> {
+    let digits = text.get(..count)?;
+    if !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    digits
+        .parse()
+        .ok()
+        .filter(|value| (min..=max).contains(value))
+}
+
+// The days since the Unix epoch of a proleptic Gregorian date, after Howard Hinnant's
+// `days_from_civil`
+pub(crate) fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
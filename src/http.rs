@@ -0,0 +1,66 @@
+use std::io::BufRead;
+use std::path::Path;
+
+// Redirects followed before giving up, as curl's `--max-redirs` would
+#[cfg(feature = "http")]
+const MAX_REDIRECTS: u32 = 5;
+
+// Whether a path given to search is a URL to fetch rather than a file
+pub(crate) fn is_url(path: &Path) -> bool {
+    path.to_str()
+        .is_some_and(|path| path.starts_with("http://") || path.starts_with("https://"))
+}
+
+// Fetches a URL with a GET, sending `headers` along, following redirects, and reads its body as
+// it arrives. It goes through `ureq`, with HTTPS by rustls, which verifies the server's
+// certificate against those the system trusts; a gzip-encoded body is decompressed.
+#[cfg(feature = "http")]
+pub(crate) fn get(
+    url: &str,
+    headers: &[(String, String)],
+) -> std::io::Result<Box<dyn BufRead + Send>> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .max_redirects(MAX_REDIRECTS)
+        // Told apart below, so the error names the status
+        .http_status_as_error(false)
+        .tls_config(
+            ureq::tls::TlsConfig::builder()
+                .root_certs(ureq::tls::RootCerts::PlatformVerifier)
+                .build(),
+        )
+        .user_agent(concat!(
+            env!("CARGO_PKG_NAME"),
+            "/",
+            env!("CARGO_PKG_VERSION")
+        ))
+        .build()
+        .into();
+    let mut request = agent.get(url);
+    for (name, value) in headers {
+        request = request.header(name, value);
+    }
+    let response = request.call().map_err(ureq::Error::into_io)?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(std::io::Error::other(format!(
+            "the server answered {} {}",
+            status.as_u16(),
+            status.canonical_reason().unwrap_or_default()
+        )));
+    }
+    Ok(Box::new(std::io::BufReader::new(
+        response.into_body().into_reader(),
+    )))
+}
+
+// Without the `http` feature, URLs are reported like files that can't be read
+#[cfg(not(feature = "http"))]
+pub(crate) fn get(
+    _url: &str,
+    _headers: &[(String, String)],
+) -> std::io::Result<Box<dyn BufRead + Send>> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "fetching URLs isn't included in this build, rebuild with `--features http`",
+    ))
+}
@@ -1,7 +1,14 @@
+use clap::Parser;
+use search_rs::{
+    BinaryFiles, ColorChoice, Colors, ColumnRange, Config, Encoding, Engine, FindAction, Heading,
+    MatchMode, OutputFormat, PathStyle, STDIN_PATH, Search, SearchError, SortKey, StatsTarget,
+    Summary, WrapMode, parse_file_size, parse_file_time, parse_labeled_pattern, read_path_list,
+};
 use std::cmp::min;
-use clap::{Parser, arg};
-use search_rs::{Config, FindAction, Search, SearchError};
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::{BufReader, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::str::FromStr;
 
 #[derive(Parser, Debug)]
@@ -11,47 +18,777 @@ use std::str::FromStr;
     about = "A parallel search utility for files and directories"
 )]
 struct Args {
-    /// The pattern to search for
-    #[arg(required = true)]
-    pattern: String,
+    /// The pattern to search for. When patterns are given with -e or --expr, or with --files, this is the first path instead
+    #[arg(required_unless_present_any = ["patterns", "expr", "structural", "files"])]
+    pattern: Option<String>,
 
-    /// The path to the file or directory to search in
-    #[arg(required = true)]
-    path: PathBuf,
+    /// The files and directories to search in, http:// or https:// URLs to fetch, or '-' to read standard input. Left out, standard input is read when it is piped in
+    paths: Vec<PathBuf>,
+
+    /// A pattern to search for; repeat to match lines containing any of them
+    #[arg(short = 'e', long = "regexp", value_name = "PATTERN")]
+    patterns: Vec<String>,
+
+    /// Read each -e pattern as LABEL=PATTERN and tag matching lines with the label of the pattern that fired
+    #[arg(long = "labels", default_value_t = false, requires = "patterns")]
+    labels: bool,
+
+    /// Match lines satisfying a boolean expression of terms, e.g. '(error AND timeout) NOT retry'
+    #[arg(
+        long = "expr",
+        value_name = "EXPRESSION",
+        conflicts_with_all = ["patterns", "regex", "hex_pattern", "fuzzy"]
+    )]
+    expr: Option<String>,
+
+    /// Match a tree-sitter query against the syntax of Rust and Python files (needs the structural feature)
+    #[arg(
+        long = "structural",
+        value_name = "QUERY",
+        conflicts_with_all = ["patterns", "expr", "regex", "hex_pattern", "fuzzy"]
+    )]
+    structural: Option<String>,
 
     /// Perform case-insensitive search
-    #[arg(short = 'i', long = "ignore-case", required = false, default_value_t = false)]
+    #[arg(
+        short = 'i',
+        long = "ignore-case",
+        required = false,
+        default_value_t = false
+    )]
     case_insensitive: bool,
 
-    /// Action to perform: 'print' (print matching lines), 'file' (print file name), 'boolean' (indicate if matches exist)
+    /// Search case-insensitively unless a pattern contains an uppercase character
+    #[arg(
+        short = 'S',
+        long = "smart-case",
+        default_value_t = false,
+        conflicts_with = "case_insensitive"
+    )]
+    smart_case: bool,
+
+    /// Ignore diacritics, so 'resume' also matches 'résumé'
+    #[arg(long = "ignore-diacritics", default_value_t = false)]
+    ignore_diacritics: bool,
+
+    /// Only match within these 1-based character columns of each line: START-END, START- or -END
+    #[arg(long = "match-columns", value_name = "RANGE")]
+    match_columns: Option<String>,
+
+    /// Select lines that do not match the pattern
+    #[arg(short = 'v', long = "invert-match", default_value_t = false)]
+    invert_match: bool,
+
+    /// Only match the pattern as a whole word, bounded by non-word characters
+    #[arg(short = 'w', long = "word-regexp", default_value_t = false)]
+    word_regexp: bool,
+
+    /// Only match when the pattern matches the entire line
+    #[arg(short = 'x', long = "line-regexp", default_value_t = false)]
+    line_regexp: bool,
+
+    /// Match lines containing anything within Levenshtein distance NUM of the pattern. With --regex,
+    /// anything within NUM errors of a string the regex matches (anchors are not supported)
+    #[arg(
+        long = "fuzzy",
+        value_name = "NUM",
+        conflicts_with_all = ["hex_pattern", "word_regexp", "line_regexp"]
+    )]
+    fuzzy: Option<usize>,
+
+    /// Treat the pattern as a regular expression
+    #[arg(short = 'E', long = "regex", default_value_t = false)]
+    regex: bool,
+
+    /// Regex engine: 'default', or 'pcre2' for lookaround and backreferences (needs the pcre2 feature)
+    #[arg(long = "engine", default_value = "default")]
+    engine: String,
+
+    /// Let regex patterns match across lines, e.g. 'foo\nbar' or '(?s)start.*?end'. A match
+    /// may span at most --chunk-size lines
+    #[arg(
+        short = 'U',
+        long = "multiline",
+        default_value_t = false,
+        requires = "regex"
+    )]
+    multiline: bool,
+
+    /// Treat the pattern as a hex ('DE AD BE EF') or escaped ('\x00\xff') byte sequence, scan raw bytes and print match offsets
+    #[arg(
+        long = "hex-pattern",
+        visible_alias = "hex",
+        default_value_t = false,
+        conflicts_with = "regex"
+    )]
+    hex_pattern: bool,
+
+    /// Print matching lines with each match rewritten by TEMPLATE, which may refer to capture groups as $1 or ${name}
+    #[arg(short = 'r', long = "replace", value_name = "TEMPLATE")]
+    replace: Option<String>,
+
+    /// Print only the matched parts of matching lines, one per line
+    #[arg(
+        short = 'o',
+        long = "only-matching",
+        default_value_t = false,
+        conflicts_with_all = ["replace", "only_group"]
+    )]
+    only_matching: bool,
+
+    /// Follow each printed file name with a NUL byte instead of a newline or separator, for xargs -0
+    #[arg(short = '0', long = "null", default_value_t = false)]
+    null: bool,
+
+    /// End every printed line with a NUL byte instead of a newline
+    #[arg(long = "null-records", default_value_t = false)]
+    null_records: bool,
+
+    /// Print SEP between groups of context lines that aren't adjacent, instead of '--'
+    #[arg(long = "group-separator", value_name = "SEP", default_value = "--")]
+    group_separator: String,
+
+    /// Print nothing between groups of context lines
+    #[arg(long = "no-group-separator", default_value_t = false)]
+    no_group_separator: bool,
+
+    /// Separate the path and numbers of a matching line from the rest with SEP, instead of ':'
+    #[arg(
+        long = "field-match-separator",
+        value_name = "SEP",
+        default_value = ":"
+    )]
+    field_match_separator: String,
+
+    /// Separate the path and numbers of a context line from the rest with SEP, instead of '-'
+    #[arg(
+        long = "field-context-separator",
+        value_name = "SEP",
+        default_value = "-"
+    )]
+    field_context_separator: String,
+
+    /// Print each file's path once above its numbered lines, with a blank line between files.
+    /// This is the default when printing a directory's results to a terminal
+    #[arg(
+        long = "heading",
+        default_value_t = false,
+        conflicts_with = "no_heading"
+    )]
+    heading: bool,
+
+    /// Print the path in front of every line of a directory's results, even on a terminal
+    #[arg(long = "no-heading", default_value_t = false)]
+    no_heading: bool,
+
+    /// Print only the text of capture group NUM of each match, one per line (0 is the whole match)
+    #[arg(long = "only-group", value_name = "NUM", conflicts_with = "replace")]
+    only_group: Option<usize>,
+
+    /// Action to perform: 'print' (print matching lines), 'file' (print the files with matches), 'boolean' (indicate if matches exist), 'report' (per-file counts and lines), 'count' (number of matching lines), 'files-without-match' (files with no match), 'names' (files whose path matches), 'files' (files that would be searched)
     #[arg(short = 'a', long = "action", default_value = "print")]
     action: String,
 
+    /// Print each file that contains a match, once (same as '--action file')
+    #[arg(
+        short = 'l',
+        long = "files-with-matches",
+        default_value_t = false,
+        conflicts_with_all = ["report", "count", "files_without_match"]
+    )]
+    files_with_matches: bool,
+
+    /// Print the searched files that contain no match (same as '--action files-without-match')
+    #[arg(
+        short = 'L',
+        long = "files-without-match",
+        default_value_t = false,
+        conflicts_with_all = ["report", "count"]
+    )]
+    files_without_match: bool,
+
+    /// Match the pattern against the paths of the files found instead of their contents, like find (same as '--action names')
+    #[arg(
+        long = "names",
+        default_value_t = false,
+        conflicts_with_all = ["report", "count", "files_with_matches", "files_without_match"]
+    )]
+    names: bool,
+
+    /// Print the files that would be searched, without searching them (same as '--action files')
+    #[arg(
+        long = "files",
+        default_value_t = false,
+        conflicts_with_all = ["patterns", "expr", "structural", "report", "count", "files_with_matches", "files_without_match", "names"]
+    )]
+    files: bool,
+
+    /// Print the number of matching lines instead of the lines (same as '--action count')
+    #[arg(long = "count", default_value_t = false, conflicts_with = "report")]
+    count: bool,
+
+    /// Print the number of matches rather than matching lines, so a line with three matches counts 3 (implies --count)
+    #[arg(
+        long = "count-matches",
+        default_value_t = false,
+        conflicts_with = "report"
+    )]
+    count_matches: bool,
+
+    /// With --count over a directory, also print the files without matches, with a count of 0
+    #[arg(long = "include-zero", default_value_t = false)]
+    include_zero: bool,
+
+    /// Also search zstd, xz and bzip2 files, decompressed in-process; a format whose Cargo feature
+    /// is off is reported as an error
+    #[arg(short = 'z', long = "search-zip", default_value_t = false)]
+    search_zip: bool,
+
+    /// Open archives nested in archives up to NUM levels deep; 0 searches them as plain files
+    #[arg(long = "archive-depth", value_name = "NUM", default_value_t = 1)]
+    archive_depth: usize,
+
+    /// Run each file through COMMAND, given the file's path and the file on its stdin, and search what it writes instead, like a script running 'pdftotext "$1" -'
+    #[arg(long = "pre", value_name = "COMMAND")]
+    pre: Option<String>,
+
+    /// Only run --pre on the files matching GLOB (repeatable, '!' to leave files out)
+    #[arg(long = "pre-glob", value_name = "GLOB", requires = "pre")]
+    pre_globs: Vec<String>,
+
+    /// Map files into memory and search them in place, rather than reading them line by line; faster for very large files
+    #[arg(long = "mmap", default_value_t = false)]
+    mmap: bool,
+
+    /// The S3 API that s3://bucket/prefix paths are read from, like http://localhost:9000 for MinIO, rather than AWS's (also AWS_ENDPOINT_URL)
+    #[arg(long = "s3-endpoint", value_name = "URL")]
+    s3_endpoint: Option<String>,
+
+    /// Show the running number of matches on stderr while searching
+    #[arg(long = "progress", default_value_t = false)]
+    progress: bool,
+
+    /// Once done, print statistics about the run to stderr: files, bytes, lines, matches, time and matcher thread utilization
+    #[arg(long = "stats", default_value_t = false)]
+    stats: bool,
+
+    /// Once done, write the statistics as one JSON document, with the reason each file was skipped, to stderr or, on unix, to file descriptor FD
+    #[arg(
+        long = "stats-json",
+        value_name = "FD",
+        num_args = 0..=1,
+        default_missing_value = "stderr"
+    )]
+    stats_json: Option<String>,
+
+    /// Print a report of each matching file with its match count and matching lines (same as '--action report')
+    #[arg(long = "report", default_value_t = false)]
+    report: bool,
+
+    /// Output format: 'text', 'json' (an array of match objects), 'ndjson' (a stream of JSON events, one per line), 'csv', 'tsv', 'vimgrep' or 'sarif' (a SARIF 2.1.0 log for code-scanning tools)
+    #[arg(long = "output", default_value = "text")]
+    output: String,
+
+    /// Print every match as path:line:column:text, for Vim's quickfix list and editor problem matchers (same as '--output vimgrep')
+    #[arg(long = "vimgrep", default_value_t = false)]
+    vimgrep: bool,
+
+    /// Fit long matching lines to the terminal width: 'truncate' or 'wrap' (ignored when not a TTY)
+    #[arg(long = "wrap")]
+    wrap: Option<String>,
+
+    /// When to color matches, paths and line numbers: 'auto' (when printing to a terminal), 'always' or 'never'.
+    /// Colors are configured with SEARCH_COLORS, e.g. 'mt=01;31:fn=35:ln=32:cn=32:bn=32:se=36'
+    #[arg(long = "color", default_value = "auto")]
+    color: String,
+
+    /// When to make printed paths clickable OSC 8 hyperlinks: 'auto' (in terminals known to support them), 'always' or 'never'
+    #[arg(long = "hyperlink", default_value = "auto")]
+    hyperlink: String,
+
+    /// Prefix each printed line with its line number
+    #[arg(short = 'n', long = "line-number", default_value_t = false)]
+    line_number: bool,
+
+    /// Prefix each matching line with the 1-based column of its first match
+    #[arg(long = "column", default_value_t = false)]
+    column: bool,
+
+    /// Prefix each printed line with the byte offset at which it starts in its file (with --only-group, where each group starts)
+    #[arg(short = 'b', long = "byte-offset", default_value_t = false)]
+    byte_offset: bool,
+
+    /// Print nothing, stop at the first match and only report through the exit code whether there was one
+    #[arg(short = 'q', long = "quiet", default_value_t = false)]
+    quiet: bool,
+
+    /// Print every line, highlighting the matches, instead of only the matching lines
+    #[arg(
+        long = "passthru",
+        default_value_t = false,
+        conflicts_with_all = ["only_matching", "only_group"]
+    )]
+    passthru: bool,
+
+    /// Keep the file open at its end and search the lines appended to it as they come, like tail -f, reopening it when it is truncated or rotated
+    #[arg(long = "follow-file", default_value_t = false)]
+    follow_file: bool,
+
+    /// After searching, keep watching the files and directories, and search the files that change or appear again, printing the matching lines they didn't have
+    #[arg(long = "watch", default_value_t = false)]
+    watch: bool,
+
+    /// Report the files of a directory in ascending order of KEY: path, modified, size or matches
+    #[arg(long = "sort", value_name = "KEY", conflicts_with = "sortr")]
+    sort: Option<String>,
+
+    /// Like --sort, in descending order
+    #[arg(long = "sortr", value_name = "KEY")]
+    sortr: Option<String>,
+
+    /// Print each distinct matching line only the first time it is found
+    #[arg(long = "dedup", default_value_t = false)]
+    dedup: bool,
+
+    /// Like --dedup, prefixing each line with how many times it was found, as uniq -c does
+    #[arg(long = "dedup-count", default_value_t = false)]
+    dedup_count: bool,
+
+    /// Write the results to PATH, which is only replaced once they are complete, instead of to stdout
+    #[arg(long = "output-file", value_name = "PATH")]
+    output_file: Option<PathBuf>,
+
+    /// Search binary files (those with a NUL byte near the start), and print their matching lines like any other
+    #[arg(long = "text", default_value_t = false, conflicts_with = "binary")]
+    text: bool,
+
+    /// Search the binary files found in directories too, printing `Binary file X matches` instead of their matching lines
+    #[arg(long = "binary", default_value_t = false)]
+    binary: bool,
+
+    /// After the results, print a summary of them: 'top=N' lists the N files with the most matches, 'histogram=minute' or 'histogram=hour' counts the matches in each minute or hour by the timestamps of the lines
+    #[arg(long = "summary", value_name = "SUMMARY")]
+    summary: Option<String>,
+
+    /// Where a histogram finds the time of each line, with %Y, %m, %b, %d, %H, %M and %S (default '%Y-%m-%d %H:%M')
+    #[arg(long = "timestamp-format", value_name = "FORMAT")]
+    timestamp_format: Option<String>,
+
+    /// Print each line through TEMPLATE, with placeholders {path}, {line}, {column}, {text}, {match}, {pattern}, {offset} and {mtime}
+    #[arg(long = "format", value_name = "TEMPLATE")]
+    format: Option<String>,
+
+    /// Also search the paths listed in FILE, one per line, or in standard input with '-'
+    #[arg(long = "files-from", value_name = "FILE")]
+    files_from: Option<PathBuf>,
+
+    /// Like --files-from, with the paths separated by NUL bytes, as find -print0 prints them
+    #[arg(
+        long = "files-from0",
+        value_name = "FILE",
+        conflicts_with = "files_from"
+    )]
+    files_from0: Option<PathBuf>,
+
+    /// Print paths 'relative' to the current directory, 'absolute' or 'canonical' (absolute with symlinks resolved), however the path to search was given
+    #[arg(long = "path-style", value_name = "STYLE")]
+    path_style: Option<String>,
+
+    /// Read files as 'auto' (UTF-16 when it has a BOM or looks like it, UTF-8 otherwise, with lines that aren't UTF-8 read as Latin-1), 'utf-8', 'utf-16le', 'utf-16be' or 'latin1'
+    #[arg(long = "encoding", value_name = "ENCODING")]
+    encoding: Option<String>,
+
+    /// Stop searching each file after NUM matching lines
+    #[arg(short = 'm', long = "max-count", value_name = "NUM")]
+    max_count: Option<usize>,
+
+    /// Stop the whole search once NUM matching lines have been found, across all files
+    #[arg(long = "max-results", value_name = "NUM")]
+    max_results: Option<usize>,
+
+    /// Print NUM lines of context after each match
+    #[arg(short = 'A', long = "after-context", value_name = "NUM")]
+    after_context: Option<usize>,
+
+    /// Print NUM lines of context before each match
+    #[arg(short = 'B', long = "before-context", value_name = "NUM")]
+    before_context: Option<usize>,
+
+    /// Print NUM lines of context before and after each match
+    #[arg(short = 'C', long = "context", value_name = "NUM")]
+    context: Option<usize>,
+
+    /// Suppress error messages about unreadable files and directories
+    #[arg(short = 's', long = "no-messages", default_value_t = false)]
+    no_messages: bool,
+
+    /// In directories, only search files matching GLOB, or with !GLOB skip them; repeatable, with gitignore-style globs of which the last matching one decides
+    #[arg(short = 'g', long = "glob", value_name = "GLOB")]
+    globs: Vec<String>,
+
+    /// In directories, only search files of type TYPE, like rust, py or md; repeatable
+    #[arg(short = 't', long = "type", value_name = "TYPE")]
+    types: Vec<String>,
+
+    /// In directories, skip files of type TYPE; repeatable
+    #[arg(short = 'T', long = "type-not", value_name = "TYPE")]
+    types_not: Vec<String>,
+
+    /// Define file type NAME by GLOB, or add GLOB to it, for --type and --type-not; repeatable
+    #[arg(long = "type-add", value_name = "NAME:GLOB")]
+    type_definitions: Vec<String>,
+
+    /// In directories, skip files larger than SIZE bytes, or with a K, M or G suffix, KiB, MiB or GiB, like 10M
+    #[arg(long = "max-filesize", value_name = "SIZE")]
+    max_filesize: Option<String>,
+
+    /// In directories, only search files modified after WHEN: a time ago like 2d (s, m, h, d or w), or a UTC date like 2024-01-01, maybe with a time like 12:30
+    #[arg(long = "newer-than", value_name = "WHEN")]
+    newer_than: Option<String>,
+
+    /// In directories, only search files modified before WHEN, given like for --newer-than
+    #[arg(long = "older-than", value_name = "WHEN")]
+    older_than: Option<String>,
+
+    /// Search only the files that the file-selection filters would have skipped
+    #[arg(long = "invert-filters", default_value_t = false)]
+    invert_filters: bool,
+
+    /// Follow symlinks found in directories, to the files and directories they point to
+    #[arg(long = "follow", default_value_t = false)]
+    follow: bool,
+
+    /// Don't skip the files and directories that .gitignore, .ignore and .searchignore files ignore
+    #[arg(long = "no-ignore", default_value_t = false)]
+    no_ignore: bool,
+
+    /// An ignore file whose rules apply to every search, instead of $XDG_CONFIG_HOME/search/ignore (~/.config/search/ignore)
+    #[arg(long = "ignore-file", value_name = "PATH")]
+    ignore_file: Option<PathBuf>,
+
+    /// Also search hidden files and directories, whose names start with a dot
+    #[arg(long = "hidden", default_value_t = false)]
+    hidden: bool,
+
+    /// In directories, search at most NUM levels deep: 1 for the files directly in them, 2 for those of their subdirectories too, and so on
+    #[arg(long = "max-depth", value_name = "NUM")]
+    max_depth: Option<usize>,
+
     /// Number of parallel threads to use (0 or 1 for sequential)
     #[arg(short = 'p', long = "parallelism", default_value_t = 1)]
     parallelism: usize,
 
+    /// Number of directories listed concurrently during a directory search. This is
+    /// independent of --parallelism, which bounds how many threads match file contents
+    #[arg(long = "traversal-concurrency", default_value_t = 1)]
+    traversal_concurrency: usize,
+
     /// Chunk size for parallel processing (lines per chunk)
     #[arg(short = 'c', long = "chunk-size", default_value_t = 1000)]
     chunk_size: usize,
 }
 
-fn main() -> Result<(), SearchError> {
+// Like grep, the exit code is 0 when something matched, 1 when nothing did and 2 on error
+fn main() -> ExitCode {
+    match run() {
+        Ok(status) => ExitCode::from(status),
+        Err(err) => {
+            eprintln!("search: {err}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+// Returns the exit status, like grep's: 0 when anything matched and 1 when nothing did, but 2
+// when some file couldn't be searched, unless a quiet search already found a match
+fn run() -> Result<u8, SearchError> {
     let args = Args::parse();
-    let action = FindAction::from_str(&args.action).map_err(|_| {
+    let action_name = if args.files {
+        "files"
+    } else if args.names {
+        "names"
+    } else if args.files_with_matches {
+        "file"
+    } else if args.files_without_match {
+        "files-without-match"
+    } else if args.report {
+        "report"
+    } else if args.count || args.count_matches {
+        "count"
+    } else {
+        args.action.as_str()
+    };
+    let action = FindAction::from_str(action_name).map_err(|_| {
         SearchError::InitializationError(format!("Invalid action: {}", args.action))
     })?;
+    let output_format = if args.vimgrep {
+        OutputFormat::Vimgrep
+    } else {
+        OutputFormat::from_str(&args.output)?
+    };
+    let engine = Engine::from_str(&args.engine)?;
+    let match_columns = args
+        .match_columns
+        .as_deref()
+        .map(ColumnRange::from_str)
+        .transpose()?;
+    let color = ColorChoice::from_str(&args.color)?;
+    let hyperlinks = ColorChoice::from_str(&args.hyperlink)?;
+    // Kept with the tool's configuration, as git keeps its own in ~/.config/git/ignore
+    let global_ignore = args.ignore_file.or_else(|| {
+        let config_home = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(config_home.join("search").join("ignore"))
+    });
+    let colors = match std::env::var("SEARCH_COLORS") {
+        Ok(spec) => Colors::from_str(&spec)?,
+        Err(_) => Colors::default(),
+    };
+    let wrap = args.wrap.as_deref().map(WrapMode::from_str).transpose()?;
+    let sort = match (&args.sort, &args.sortr) {
+        (Some(key), _) => Some((SortKey::from_str(key)?, false)),
+        (None, Some(key)) => Some((SortKey::from_str(key)?, true)),
+        (None, None) => None,
+    };
     let max_parallelism = std::thread::available_parallelism().map_or(1, |v| v.get());
     let parallelism_to_use = min(args.parallelism, max_parallelism);
+    // With -e or --expr the patterns come from the flags, so the only positional argument is the path.
+    // --files has no pattern at all.
+    let from_flags =
+        !args.patterns.is_empty() || args.expr.is_some() || args.structural.is_some() || args.files;
+    let list = match (&args.files_from, &args.files_from0) {
+        (Some(list), _) => Some((list, b'\n')),
+        (None, Some(list)) => Some((list, b'\0')),
+        (None, None) => None,
+    };
+    let listed = match list {
+        Some((list, separator)) if list == Path::new(STDIN_PATH) => {
+            read_path_list(std::io::stdin().lock(), separator)?
+        }
+        Some((list, separator)) => read_path_list(
+            BufReader::new(File::open(list).map_err(SearchError::ReadError)?),
+            separator,
+        )?,
+        None => Vec::new(),
+    };
+    // A list read from standard input leaves nothing else to read there
+    let piped = !std::io::stdin().is_terminal() && list.is_none();
+    let stdin = || vec![PathBuf::from(STDIN_PATH)];
+    let (patterns, mut paths) = match (!from_flags, args.pattern, args.paths) {
+        (true, Some(pattern), paths) if !paths.is_empty() || list.is_some() => {
+            (vec![pattern], paths)
+        }
+        (true, Some(pattern), _) if piped => (vec![pattern], stdin()),
+        (false, Some(first), paths) => (
+            args.patterns,
+            std::iter::once(PathBuf::from(first)).chain(paths).collect(),
+        ),
+        (false, None, _) if list.is_some() => (args.patterns, Vec::new()),
+        (false, None, _) if piped => (args.patterns, stdin()),
+        _ => {
+            return Err(SearchError::InitializationError(
+                "expected <pattern> <path>..., or only paths when patterns are given with -e or --expr, or with --files"
+                    .to_string(),
+            ));
+        }
+    };
+    paths.extend(listed);
+    // No paths would mean standard input, but an empty list has nothing to search
+    if paths.is_empty() {
+        return Ok(1);
+    }
+    let labeled = if args.labels {
+        Some(
+            patterns
+                .iter()
+                .map(|pattern| parse_labeled_pattern(pattern))
+                .collect::<Result<Vec<_>, _>>()?,
+        )
+    } else {
+        None
+    };
     let config = Config::init(
-        args.path,
-        args.pattern,
+        PathBuf::new(),
+        String::new(),
         Some(args.case_insensitive),
         Some(action),
         Some(args.chunk_size),
         Some(parallelism_to_use),
+    )
+    .with_match_mode(if args.regex {
+        MatchMode::Regex
+    } else if args.hex_pattern {
+        MatchMode::Bytes
+    } else {
+        MatchMode::Substring
+    })
+    .with_patterns(patterns)
+    .with_paths(paths)
+    .with_smart_case(args.smart_case)
+    .with_ignore_diacritics(args.ignore_diacritics)
+    .with_invert_match(args.invert_match)
+    .with_word_regexp(args.word_regexp)
+    .with_line_regexp(args.line_regexp)
+    .with_multiline(args.multiline)
+    .with_engine(engine)
+    .with_output_format(output_format)
+    .with_no_messages(args.no_messages)
+    .with_globs(args.globs)
+    .with_types(args.types)
+    .with_types_not(args.types_not)
+    .with_type_definitions(args.type_definitions)
+    .with_invert_filters(args.invert_filters)
+    .with_follow(args.follow)
+    .with_no_ignore(args.no_ignore)
+    .with_hidden(args.hidden)
+    .with_progress(args.progress)
+    .with_stats(args.stats)
+    .with_traversal_concurrency(args.traversal_concurrency)
+    .with_line_numbers(args.line_number)
+    .with_column(args.column)
+    .with_byte_offset(args.byte_offset)
+    .with_count_matches(args.count_matches)
+    .with_include_zero(args.include_zero)
+    .with_search_zip(args.search_zip)
+    .with_archive_depth(args.archive_depth)
+    .with_pre_globs(args.pre_globs)
+    .with_mmap(args.mmap)
+    .with_only_matching(args.only_matching)
+    .with_null(args.null)
+    .with_null_records(args.null_records)
+    .with_group_separator(Some(args.group_separator).filter(|_| !args.no_group_separator))
+    .with_field_separators(args.field_match_separator, args.field_context_separator)
+    .with_heading(match (args.heading, args.no_heading) {
+        (true, _) => Heading::Always,
+        (_, true) => Heading::Never,
+        _ => Heading::Auto,
+    })
+    .with_quiet(args.quiet)
+    .with_passthru(args.passthru)
+    .with_follow_file(args.follow_file)
+    .with_watch(args.watch)
+    .with_dedup(args.dedup, args.dedup_count)
+    .with_color(color, colors)
+    .with_hyperlinks(hyperlinks)
+    .with_binary_files(match (args.text, args.binary) {
+        (true, _) => BinaryFiles::Text,
+        (false, true) => BinaryFiles::Binary,
+        (false, false) => BinaryFiles::Auto,
+    })
+    .with_context(
+        args.before_context.or(args.context).unwrap_or(0),
+        args.after_context.or(args.context).unwrap_or(0),
     );
-    let search = Search::new(config);
-    search.search()?;
-    Ok(())
+    let config = match wrap {
+        Some(wrap) => config.with_wrap(wrap),
+        None => config,
+    };
+    let config = match labeled {
+        Some(labeled) => config.with_labeled_patterns(labeled),
+        None => config,
+    };
+    let config = match match_columns {
+        Some(columns) => config.with_match_columns(columns),
+        None => config,
+    };
+    let config = match sort {
+        Some((key, descending)) => config.with_sort(key, descending),
+        None => config,
+    };
+    let config = match args.output_file {
+        Some(path) => config.with_output_file(path),
+        None => config,
+    };
+    let config = match args.summary.as_deref().map(Summary::from_str).transpose()? {
+        Some(summary) => config.with_summary(summary),
+        None => config,
+    };
+    let config = match args.stats_json {
+        Some(target) => config.with_stats_json(StatsTarget::from_str(&target)?),
+        None => config,
+    };
+    let config = match args.path_style {
+        Some(style) => config.with_path_style(PathStyle::from_str(&style)?),
+        None => config,
+    };
+    let config = match args.encoding {
+        Some(encoding) => config.with_encoding(Encoding::from_str(&encoding)?),
+        None => config,
+    };
+    let config = match args.timestamp_format {
+        Some(format) => config.with_timestamp_format(format),
+        None => config,
+    };
+    let config = match args.format {
+        Some(format) => config.with_format(format),
+        None => config,
+    };
+    let config = match args.max_count {
+        Some(max_count) => config.with_max_count(max_count),
+        None => config,
+    };
+    let config = match args.max_depth {
+        Some(max_depth) => config.with_max_depth(max_depth),
+        None => config,
+    };
+    let config = match args.max_filesize {
+        Some(size) => config.with_max_filesize(parse_file_size(&size)?),
+        None => config,
+    };
+    let now = std::time::SystemTime::now();
+    let config = match args.newer_than {
+        Some(when) => config.with_newer_than(parse_file_time(&when, now)?),
+        None => config,
+    };
+    let config = match args.older_than {
+        Some(when) => config.with_older_than(parse_file_time(&when, now)?),
+        None => config,
+    };
+    let config = match args.max_results {
+        Some(max_results) => config.with_max_results(max_results),
+        None => config,
+    };
+    let config = match args.only_group {
+        Some(group) => config.with_only_group(group),
+        None => config,
+    };
+    let config = match args.replace {
+        Some(template) => config.with_replace(template),
+        None => config,
+    };
+    let config = match args.structural {
+        Some(query) => config.with_structural(query),
+        None => config,
+    };
+    let config = match args.expr {
+        Some(expr) => config.with_expr(expr),
+        None => config,
+    };
+    let config = match args.fuzzy {
+        Some(max_distance) => config.with_fuzzy(max_distance),
+        None => config,
+    };
+    let config = match global_ignore {
+        Some(path) => config.with_global_ignore(path),
+        None => config,
+    };
+    let config = match args.pre {
+        Some(command) => config.with_pre(command),
+        None => config,
+    };
+    let config = match args.s3_endpoint {
+        Some(endpoint) => config.with_s3_endpoint(endpoint),
+        None => config,
+    };
+    let search = Search::new(config)?;
+    let found = search.search()?;
+    Ok(if search.failed() && !(args.quiet && found) {
+        2
+    } else if found {
+        0
+    } else {
+        1
+    })
 }
@@ -0,0 +1,962 @@
+use crate::template::Fields;
+use crate::{
+    Bucket, FindAction, Heading, LineKind, OutputFormat, Search, SearchError, SearchMatch, Summary,
+    fit_to_width, histogram,
+};
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, SystemTime};
+
+// When to use colors or hyperlinks, which are meant for reading results on a terminal
+pub enum ColorChoice {
+    // Only when the results go to a terminal (and for colors, NO_COLOR isn't set)
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = SearchError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(SearchError::InitializationError(format!(
+                "color choice {s} is invalid"
+            ))),
+        }
+    }
+}
+
+// The SGR parameters of each part of a printed line, configured like GREP_COLORS:
+// `mt=01;31:fn=35:ln=32:cn=32:bn=32:se=36`. Parts left out keep their default, and an empty
+// value leaves that part uncolored.
+pub struct Colors {
+    matched: String,
+    path: String,
+    line_number: String,
+    column: String,
+    byte_offset: String,
+    separator: String,
+}
+
+impl Default for Colors {
+    fn default() -> Colors {
+        Colors {
+            matched: "01;31".to_string(),
+            path: "35".to_string(),
+            line_number: "32".to_string(),
+            column: "32".to_string(),
+            byte_offset: "32".to_string(),
+            separator: "36".to_string(),
+        }
+    }
+}
+
+impl FromStr for Colors {
+    type Err = SearchError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| {
+            SearchError::InitializationError(format!("colors {s} are invalid: {reason}"))
+        };
+        let mut colors = Colors::default();
+        for setting in s.split(':').filter(|setting| !setting.is_empty()) {
+            let (name, value) = setting
+                .split_once('=')
+                .ok_or_else(|| invalid("expected NAME=SGR"))?;
+            if !value.chars().all(|c| c.is_ascii_digit() || c == ';') {
+                return Err(invalid("SGR parameters are digits separated by ';'"));
+            }
+            let part = match name {
+                "mt" => &mut colors.matched,
+                "fn" => &mut colors.path,
+                "ln" => &mut colors.line_number,
+                "cn" => &mut colors.column,
+                "bn" => &mut colors.byte_offset,
+                "se" => &mut colors.separator,
+                _ => return Err(invalid(&format!("unknown part {name}"))),
+            };
+            *part = value.to_string();
+        }
+        Ok(colors)
+    }
+}
+
+impl Colors {
+    fn paint(sgr: &str, text: &str) -> String {
+        match sgr.is_empty() || text.is_empty() {
+            true => text.to_string(),
+            false => format!("\x1b[{sgr}m{text}\x1b[0m"),
+        }
+    }
+
+    // Paints the spans of `text`, which are in order and don't overlap, in the match color
+    fn highlight(&self, text: &str, spans: &[(usize, usize)]) -> String {
+        let mut painted = String::with_capacity(text.len());
+        let mut end = 0;
+        for &(start, span_end) in spans {
+            painted.push_str(&text[end..start]);
+            painted.push_str(&Colors::paint(&self.matched, &text[start..span_end]));
+            end = span_end;
+        }
+        painted.push_str(&text[end..]);
+        painted
+    }
+}
+
+// The colors to print with, if any
+fn colors(search: &Search) -> Option<&Colors> {
+    let enabled = match search.config.color {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => search.writes_to_terminal() && std::env::var_os("NO_COLOR").is_none(),
+    };
+    enabled.then_some(&search.config.colors)
+}
+
+// Whether to make printed paths clickable. Terminals that understand OSC 8 hyperlinks are
+// recognized by what they set in the environment; the others would show the escapes.
+fn hyperlinks(search: &Search) -> bool {
+    match search.config.hyperlinks {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            let env = |name: &str| std::env::var(name).unwrap_or_default();
+            let supported = matches!(
+                env("TERM_PROGRAM").as_str(),
+                "iTerm.app" | "WezTerm" | "vscode"
+            ) || env("VTE_VERSION")
+                .parse::<u32>()
+                .is_ok_and(|version| version >= 5000)
+                || !env("WT_SESSION").is_empty()
+                || env("TERM") == "xterm-kitty";
+            search.writes_to_terminal() && supported
+        }
+    }
+}
+
+// Wraps `text` in an OSC 8 hyperlink to the file, at `line` if given
+fn hyperlink(path: &Path, line: Option<usize>, text: &str) -> String {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut url = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                url.push(byte as char)
+            }
+            _ => url.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    if let Some(line) = line {
+        url.push_str(&format!("#L{line}"));
+    }
+    format!("\x1b]8;;{url}\x1b\\{text}\x1b]8;;\x1b\\")
+}
+
+// Writes the results of a search as text, in the shape the action asks for
+pub(crate) fn write_text<W: Write>(
+    search: &Search,
+    matches: &[SearchMatch],
+    searched: &[PathBuf],
+    found: bool,
+    out: &mut W,
+) -> Result<(), SearchError> {
+    // TODO - why here? we can run out of memory... its just plain inefficient
+    let record_end = if search.config.null_records {
+        "\0"
+    } else {
+        "\n"
+    };
+    // Like grep -Z, a NUL takes the place of whatever would follow a file name
+    let after_path = |end: &str| match search.config.null {
+        true => String::from("\0"),
+        false => end.to_string(),
+    };
+    let hyperlinks = hyperlinks(search);
+    let link = |path: &Path, line: Option<usize>, text: String| match hyperlinks {
+        true => hyperlink(path, line, &text),
+        false => text,
+    };
+    match search.config.action {
+        FindAction::PrintLine if search.template.is_some() => {
+            write_templated(search, matches, record_end, out)?
+        }
+        FindAction::PrintLine => {
+            let width = search.terminal_width();
+            // Fitting lines to the terminal counts the columns of plain text, so wrapped output
+            // is left uncolored and unlinked
+            let colors = colors(search).filter(|_| width.is_none());
+            let link = |path: &Path, line: Option<usize>, text: String| match width {
+                Some(_) => text,
+                None => link(path, line, text),
+            };
+            let paint = |part: fn(&Colors) -> &String, text: &str| match colors {
+                Some(colors) => Colors::paint(part(colors), text),
+                None => text.to_string(),
+            };
+            // With headings each file's path is printed once above its lines, and the lines
+            // are numbered instead; otherwise, like grep with several files, lines from a
+            // directory or several paths say which file they're from
+            let heading = match search.config.heading {
+                Heading::Always => true,
+                Heading::Never => false,
+                Heading::Auto => search.writes_to_terminal() && search.searches_many(),
+            };
+            let with_path = !heading && search.searches_many();
+            let line_numbers = search.config.line_numbers || heading;
+            // Like grep, groups of lines that aren't adjacent are separated, by `--` by default
+            let group_separator = search.config.group_separator.as_deref().filter(|_| {
+                (search.config.before_context > 0 || search.config.after_context > 0)
+                    && search.config.only_group.is_none()
+                    && !search.config.only_matching
+            });
+            let mut previous: Option<(&Path, usize)> = None;
+            // The binary file last reported, whose other lines are left out
+            let mut binary: Option<&Path> = None;
+            for search_match in matches.iter() {
+                let is_match = matches!(search_match.kind, LineKind::Match);
+                if search.is_binary(&search_match.path) {
+                    if is_match && binary != Some(search_match.path.as_path()) {
+                        let path = search_match.path.display().to_string();
+                        let path = link(
+                            &search_match.path,
+                            None,
+                            paint(|colors| &colors.path, &path),
+                        );
+                        write!(out, "Binary file {path} matches{record_end}")
+                            .map_err(SearchError::WriteError)?;
+                        binary = Some(search_match.path.as_path());
+                    }
+                    continue;
+                }
+                let current = (search_match.path.as_path(), search_match.line_number);
+                let new_file = previous.is_none_or(|(path, _)| path != current.0);
+                if heading && new_file {
+                    if previous.is_some() {
+                        write!(out, "{record_end}").map_err(SearchError::WriteError)?;
+                    }
+                    let path = search_match.path.display().to_string();
+                    let path = link(
+                        &search_match.path,
+                        None,
+                        paint(|colors| &colors.path, &path),
+                    );
+                    let end = after_path(record_end);
+                    write!(out, "{path}{end}").map_err(SearchError::WriteError)?;
+                } else if let Some(group_separator) = group_separator
+                    && previous.is_some_and(|(path, line_number)| {
+                        path != current.0 || line_number + 1 != current.1
+                    })
+                {
+                    let separator = paint(|colors| &colors.separator, group_separator);
+                    write!(out, "{separator}{record_end}").map_err(SearchError::WriteError)?;
+                }
+                previous = Some(current);
+                // Each text comes with where it starts within the line
+                let texts = match (search_match.byte_offset, &search.capturing) {
+                    (Some(offset), _) => vec![(0, offset.to_string())],
+                    // Like grep -o, extracting leaves no room for context lines
+                    (None, Some(regex)) if search.config.only_group.is_some() => match is_match {
+                        true => search.extract_group(regex, &search_match.line),
+                        false => Vec::new(),
+                    },
+                    (None, _) if search.config.only_matching => match is_match {
+                        true => search
+                            .matcher
+                            .find_spans(&search_match.line)
+                            .into_iter()
+                            .filter(|(start, end)| start < end)
+                            .map(|(start, end)| (start, search_match.line[start..end].to_string()))
+                            .collect(),
+                        false => Vec::new(),
+                    },
+                    (None, Some(regex)) if is_match => {
+                        let template = search.config.replace.as_deref().unwrap_or_default();
+                        let replaced = regex.replace_all(&search_match.line, template);
+                        vec![(0, replaced.into_owned())]
+                    }
+                    (None, _) => vec![(0, search_match.line.clone())],
+                };
+                // Whole matching lines get their matches highlighted, extracted matches are
+                // highlighted whole, and rewritten text isn't
+                let highlights = |start: usize, text: &str| match (colors, is_match) {
+                    (Some(colors), true)
+                        if search.config.only_group.is_some() || search.config.only_matching =>
+                    {
+                        colors.highlight(text, &[(0, text.len())])
+                    }
+                    (Some(colors), true)
+                        if start == 0
+                            && search_match.byte_offset.is_none()
+                            && search.config.replace.is_none() =>
+                    {
+                        colors.highlight(text, &search.matcher.find_spans(text))
+                    }
+                    _ => text.to_string(),
+                };
+                // Same convention as grep by default: ':' after the path and number of a match, '-'
+                // for context
+                let separator = match is_match {
+                    true => &search.config.match_separator,
+                    false => &search.config.context_separator,
+                };
+                let separator = paint(|colors| &colors.separator, separator);
+                for (start, text) in texts {
+                    let mut line = highlights(start, &text);
+                    if let (true, Some(offset)) =
+                        (search.config.byte_offset, search_match.line_offset)
+                    {
+                        let offset = (offset + start).to_string();
+                        line = format!(
+                            "{}{separator}{line}",
+                            paint(|colors| &colors.byte_offset, &offset)
+                        );
+                    }
+                    if let (true, Some(column)) = (search.config.column, search_match.column) {
+                        let column = column.to_string();
+                        line = format!(
+                            "{}{separator}{line}",
+                            paint(|colors| &colors.column, &column)
+                        );
+                    }
+                    if line_numbers {
+                        let number = search_match.line_number.to_string();
+                        line = format!(
+                            "{}{separator}{line}",
+                            paint(|colors| &colors.line_number, &number)
+                        );
+                    }
+                    if with_path {
+                        let path = search_match.path.display().to_string();
+                        let separator = match search.config.null {
+                            true => String::from("\0"),
+                            false => separator.clone(),
+                        };
+                        let path = link(
+                            &search_match.path,
+                            Some(search_match.line_number),
+                            paint(|colors| &colors.path, &path),
+                        );
+                        line = format!("{path}{separator}{line}");
+                    }
+                    if let Some(label) = &search_match.label {
+                        line = format!("[{label}] {line}");
+                    }
+                    // Counted like `uniq -c`
+                    if search.config.dedup_counts && is_match {
+                        let count = search.occurrences_of(&search_match.line);
+                        line = format!("{count:>7} {line}");
+                    }
+                    match (width, &search.config.wrap) {
+                        (Some(width), Some(wrap)) => {
+                            for row in fit_to_width(&line, width, wrap) {
+                                write!(out, "{row}{record_end}")
+                                    .map_err(SearchError::WriteError)?;
+                            }
+                        }
+                        _ => write!(out, "{line}{record_end}").map_err(SearchError::WriteError)?,
+                    }
+                }
+            }
+        }
+        FindAction::PrintFileName => {
+            let matched: BTreeSet<&Path> = matches
+                .iter()
+                .filter(|search_match| matches!(search_match.kind, LineKind::Match))
+                .map(|search_match| search_match.path.as_path())
+                .collect();
+            let end = after_path(record_end);
+            for path in matched {
+                let path = link(path, None, path.display().to_string());
+                write!(out, "{path}{end}").map_err(SearchError::WriteError)?;
+            }
+        }
+        FindAction::Report => {
+            let mut by_file: BTreeMap<&Path, Vec<&SearchMatch>> = BTreeMap::new();
+            for search_match in matches.iter() {
+                if let LineKind::Match = search_match.kind {
+                    by_file
+                        .entry(search_match.path.as_path())
+                        .or_default()
+                        .push(search_match);
+                }
+            }
+            for (path, file_matches) in by_file {
+                let separator = after_path(": ");
+                let count = file_matches.len();
+                let path = link(path, None, path.display().to_string());
+                write!(out, "{path}{separator}{count}{record_end}")
+                    .map_err(SearchError::WriteError)?;
+                for search_match in file_matches {
+                    write!(
+                        out,
+                        "    {}: {}{record_end}",
+                        search_match.line_number, search_match.line
+                    )
+                    .map_err(SearchError::WriteError)?;
+                }
+            }
+        }
+        FindAction::Boolean => {
+            write!(out, "{found}{record_end}").map_err(SearchError::WriteError)?
+        }
+        // A single file gets its bare count, several files one `path:count` line per matching file
+        FindAction::Count if search.searches_many() => {
+            let mut by_file: BTreeMap<&Path, usize> = BTreeMap::new();
+            if search.config.include_zero {
+                by_file.extend(searched.iter().map(|path| (path.as_path(), 0)));
+            }
+            for search_match in matches.iter() {
+                if let LineKind::Match = search_match.kind {
+                    *by_file.entry(search_match.path.as_path()).or_default() +=
+                        search.count_of(search_match);
+                }
+            }
+            for (path, count) in by_file {
+                let separator = after_path(":");
+                let path = link(path, None, path.display().to_string());
+                write!(out, "{path}{separator}{count}{record_end}")
+                    .map_err(SearchError::WriteError)?;
+            }
+        }
+        FindAction::Count => {
+            let count: usize = matches
+                .iter()
+                .filter(|search_match| matches!(search_match.kind, LineKind::Match))
+                .map(|search_match| search.count_of(search_match))
+                .sum();
+            write!(out, "{count}{record_end}").map_err(SearchError::WriteError)?
+        }
+        FindAction::MatchNames | FindAction::ListFiles => {
+            let mut paths: Vec<&Path> = matches
+                .iter()
+                .map(|search_match| search_match.path.as_path())
+                .collect();
+            paths.sort();
+            let end = after_path(record_end);
+            for path in paths {
+                let path = link(path, None, path.display().to_string());
+                write!(out, "{path}{end}").map_err(SearchError::WriteError)?;
+            }
+        }
+        FindAction::FilesWithoutMatch => {
+            let matched: BTreeSet<&Path> = matches
+                .iter()
+                .filter(|search_match| matches!(search_match.kind, LineKind::Match))
+                .map(|search_match| search_match.path.as_path())
+                .collect();
+            let mut unmatched: Vec<&PathBuf> = searched
+                .iter()
+                .filter(|path| !matched.contains(path.as_path()))
+                .collect();
+            unmatched.sort();
+            let end = after_path(record_end);
+            for path in unmatched {
+                let path = link(path, None, path.display().to_string());
+                write!(out, "{path}{end}").map_err(SearchError::WriteError)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Every line through the `--format` template, context lines included
+fn write_templated<W: Write>(
+    search: &Search,
+    matches: &[SearchMatch],
+    record_end: &str,
+    out: &mut W,
+) -> Result<(), SearchError> {
+    let Some(template) = &search.template else {
+        return Ok(());
+    };
+    // Lines come file by file, so each file's modification time is only looked up once
+    let mut modified: Option<(&Path, Option<SystemTime>)> = None;
+    for search_match in matches.iter() {
+        let path = search_match.path.as_path();
+        if template.uses_mtime() && modified.is_none_or(|(previous, _)| previous != path) {
+            let time = std::fs::metadata(path).and_then(|metadata| metadata.modified());
+            modified = Some((path, time.ok()));
+        }
+        let first_match = match (&search_match.kind, search_match.byte_offset) {
+            (LineKind::Match, None) => search
+                .matcher
+                .find_spans(&search_match.line)
+                .first()
+                .map(|&(start, end)| &search_match.line[start..end]),
+            // Byte pattern matches are their own text
+            (LineKind::Match, Some(_)) => Some(search_match.line.as_str()),
+            (LineKind::Context, _) => None,
+        };
+        let fields = Fields {
+            first_match,
+            modified: modified.and_then(|(_, time)| time),
+        };
+        let line = template.render(search_match, &fields);
+        write!(out, "{line}{record_end}").map_err(SearchError::WriteError)?;
+    }
+    Ok(())
+}
+
+// A minimal SARIF 2.1.0 log: one run, one rule (the pattern, or one per label) and one result
+// per match
+pub(crate) fn sarif_document(search: &Search, matches: &[SearchMatch]) -> serde_json::Value {
+    let rule_id = search.config.patterns.join("|");
+    let rule_id = rule_id.as_str();
+    // Labelled patterns are rules of their own, identified by their labels. Patterns can share
+    // a label, and then the rule describes all of them.
+    let mut rules: Vec<(&str, Vec<&str>)> = Vec::new();
+    for (label, pattern) in search
+        .config
+        .labels
+        .iter()
+        .zip(search.config.patterns.iter())
+    {
+        match rules.iter_mut().find(|(id, _)| id == label) {
+            Some((_, patterns)) => patterns.push(pattern),
+            None => rules.push((label, vec![pattern])),
+        }
+    }
+    if rules.is_empty() {
+        rules.push((rule_id, Vec::new()));
+    }
+    let results: Vec<serde_json::Value> = matches
+        .iter()
+        .filter(|search_match| matches!(search_match.kind, LineKind::Match))
+        .map(|search_match| {
+            let rule_id = search_match.label.as_deref().unwrap_or(rule_id);
+            let mut region = json!({ "startLine": search_match.line_number });
+            if let Some(column) = search_match.column {
+                region["startColumn"] = json!(column);
+            }
+            if let Some(offset) = search_match.byte_offset {
+                region["byteOffset"] = json!(offset);
+                region["byteLength"] = json!(search_match.line.len());
+            }
+            // SARIF requires a non-empty message for every result
+            let text = if search_match.line.is_empty() {
+                format!("Match for {rule_id}")
+            } else {
+                search_match.line.clone()
+            };
+            let mut result = json!({
+                "ruleId": rule_id,
+                "level": "note",
+                "message": { "text": text },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": {
+                            "uri": search_match.path.to_string_lossy().replace('\\', "/")
+                        },
+                        "region": region,
+                    }
+                }],
+            });
+            if let Some(index) = rules.iter().position(|(id, _)| *id == rule_id) {
+                result["ruleIndex"] = json!(index);
+            }
+            result
+        })
+        .collect();
+    let rules: Vec<serde_json::Value> = rules
+        .iter()
+        .map(|(id, patterns)| match patterns.is_empty() {
+            true => json!({ "id": id }),
+            false => json!({ "id": id, "shortDescription": { "text": patterns.join(" | ") } }),
+        })
+        .collect();
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                }
+            },
+            "results": results,
+        }],
+    })
+}
+
+// An array with one object per matching line. The label is only there for labelled patterns.
+pub(crate) fn json_document(matches: &[SearchMatch]) -> serde_json::Value {
+    let records: Vec<serde_json::Value> = matches
+        .iter()
+        .filter(|search_match| matches!(search_match.kind, LineKind::Match))
+        .map(|search_match| {
+            let mut record = json!({
+                "path": search_match.path.display().to_string(),
+                "line_number": search_match.line_number,
+                "column": search_match.column,
+                "text": search_match.line,
+                "pattern": search_match.pattern,
+            });
+            if let Some(label) = &search_match.label {
+                record["label"] = json!(label);
+            }
+            if let Some(offset) = search_match.byte_offset {
+                record["byte_offset"] = json!(offset);
+            }
+            record
+        })
+        .collect();
+    json!(records)
+}
+
+// The field separator, row end and field escaping of CSV and TSV
+type Delimited = (&'static str, &'static str, fn(&str) -> String);
+
+fn delimited(format: &OutputFormat) -> Option<Delimited> {
+    match format {
+        OutputFormat::Csv => Some((",", "\r\n", csv_field)),
+        OutputFormat::Tsv => Some(("\t", "\n", tsv_field)),
+        _ => None,
+    }
+}
+
+// What streamed output starts with: the header row of CSV and TSV
+pub(crate) fn write_stream_start<W: Write>(
+    search: &Search,
+    out: &mut W,
+) -> Result<(), SearchError> {
+    match delimited(&search.config.output_format) {
+        Some(delimited) => write_row(&["path", "line", "column", "match"], delimited, out),
+        None => Ok(()),
+    }
+}
+
+// Writes the matches of one searched file in a streamed format, returning how many matching
+// lines were written
+pub(crate) fn write_streamed_file<W: Write>(
+    search: &Search,
+    matches: &[SearchMatch],
+    out: &mut W,
+) -> Result<usize, SearchError> {
+    let delimited = match search.config.output_format {
+        OutputFormat::Ndjson => return write_ndjson_file(search, matches, out),
+        OutputFormat::Vimgrep => return write_vimgrep_file(search, matches, out),
+        ref format => delimited(format).expect("CSV and TSV are the other streamed formats"),
+    };
+    let mut count = 0;
+    for search_match in matches {
+        if !matches!(search_match.kind, LineKind::Match) {
+            continue;
+        }
+        count += 1;
+        // A row per match on the line, holding what `-o` prints of it. Lines matched without a
+        // position of their own, like fuzzy or inverted matches, are matched whole.
+        let line = &search_match.line;
+        let mut spans: Vec<(Option<usize>, &str)> = search
+            .matcher
+            .find_spans(line)
+            .into_iter()
+            .filter(|(start, end)| start < end)
+            .map(|(start, end)| (Some(line[..start].chars().count() + 1), &line[start..end]))
+            .collect();
+        if spans.is_empty() || search.config.invert_match {
+            spans = vec![(search_match.column, line)];
+        }
+        for (column, text) in spans {
+            let row = [
+                search_match.path.display().to_string(),
+                search_match.line_number.to_string(),
+                column.map_or_else(String::new, |column| column.to_string()),
+                text.to_string(),
+            ];
+            write_row(&row, delimited, out)?;
+        }
+    }
+    Ok(count)
+}
+
+// A `path:line:column:text` line per match, so a line with two matches is written twice.
+// Lines matched without a position of their own, like fuzzy or inverted matches, get column 1.
+fn write_vimgrep_file<W: Write>(
+    search: &Search,
+    matches: &[SearchMatch],
+    out: &mut W,
+) -> Result<usize, SearchError> {
+    let mut count = 0;
+    for search_match in matches {
+        if !matches!(search_match.kind, LineKind::Match) {
+            continue;
+        }
+        count += 1;
+        let mut columns: Vec<usize> = search
+            .matcher
+            .find_spans(&search_match.line)
+            .into_iter()
+            .map(|(start, _)| search_match.line[..start].chars().count() + 1)
+            .collect();
+        if columns.is_empty() || search.config.invert_match {
+            columns = vec![1];
+        }
+        for column in columns {
+            writeln!(
+                out,
+                "{}:{}:{column}:{}",
+                search_match.path.display(),
+                search_match.line_number,
+                search_match.line
+            )
+            .map_err(SearchError::WriteError)?;
+        }
+    }
+    Ok(count)
+}
+
+// What streamed output ends with: the summary event of NDJSON
+pub(crate) fn write_stream_end<W: Write>(
+    search: &Search,
+    matches: usize,
+    searched: usize,
+    out: &mut W,
+) -> Result<(), SearchError> {
+    match search.config.output_format {
+        OutputFormat::Ndjson => write_ndjson_summary(matches, searched, out),
+        _ => Ok(()),
+    }
+}
+
+fn write_row<W: Write, S: AsRef<str>>(
+    fields: &[S],
+    (separator, row_end, escape): Delimited,
+    out: &mut W,
+) -> Result<(), SearchError> {
+    let row: Vec<String> = fields.iter().map(|text| escape(text.as_ref())).collect();
+    write!(out, "{}{row_end}", row.join(separator)).map_err(SearchError::WriteError)
+}
+
+// RFC 4180: fields with a comma, quote or line break are quoted, with quotes doubled
+fn csv_field(text: &str) -> String {
+    match text.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", text.replace('"', "\"\"")),
+        false => text.to_string(),
+    }
+}
+
+// TSV can't quote, so tabs, line breaks and backslashes are escaped as `\t`, `\n`, `\r` and `\\`
+fn tsv_field(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// The events of one file, one JSON object per line in the style of ripgrep's `--json`: `begin`,
+// a `match` or `context` event per line and `end`. Files without matches write nothing.
+// Returns the number of matching lines written.
+fn write_ndjson_file<W: Write>(
+    search: &Search,
+    matches: &[SearchMatch],
+    out: &mut W,
+) -> Result<usize, SearchError> {
+    let Some(first) = matches.first() else {
+        return Ok(0);
+    };
+    let path = first.path.display().to_string();
+    let mut events = vec![json!({ "type": "begin", "data": { "path": path } })];
+    let mut count = 0;
+    for search_match in matches {
+        let mut data = json!({
+            "path": path,
+            "line_number": search_match.line_number,
+            "absolute_offset": search_match.line_offset.or(search_match.byte_offset),
+            "text": search_match.line,
+        });
+        let kind = match search_match.kind {
+            LineKind::Match => {
+                count += 1;
+                data["pattern"] = json!(search_match.pattern);
+                data["submatches"] = json!(
+                    search
+                        .matcher
+                        .find_spans(&search_match.line)
+                        .into_iter()
+                        .map(|(start, end)| json!({
+                            "text": search_match.line[start..end],
+                            "start": start,
+                            "end": end,
+                        }))
+                        .collect::<Vec<_>>()
+                );
+                if let Some(label) = &search_match.label {
+                    data["label"] = json!(label);
+                }
+                "match"
+            }
+            LineKind::Context => "context",
+        };
+        events.push(json!({ "type": kind, "data": data }));
+    }
+    events.push(json!({ "type": "end", "data": { "path": path, "stats": { "matches": count } } }));
+    for event in events {
+        serde_json::to_writer(&mut *out, &event)
+            .map_err(|err| SearchError::WriteError(err.into()))?;
+        writeln!(out).map_err(SearchError::WriteError)?;
+    }
+    Ok(count)
+}
+
+// The closing event of an NDJSON search, with totals over every file
+fn write_ndjson_summary<W: Write>(
+    matches: usize,
+    searched: usize,
+    out: &mut W,
+) -> Result<(), SearchError> {
+    let summary = json!({
+        "type": "summary",
+        "data": { "stats": { "matches": matches, "searched": searched } },
+    });
+    serde_json::to_writer(&mut *out, &summary)
+        .map_err(|err| SearchError::WriteError(err.into()))?;
+    writeln!(out).map_err(SearchError::WriteError)
+}
+
+// The `--summary` after the results, e.g. the files with the most matches
+pub(crate) fn write_summary<W: Write>(
+    search: &Search,
+    summary: &Summary,
+    out: &mut W,
+) -> Result<(), SearchError> {
+    let top = match summary {
+        Summary::Top(top) => top,
+        Summary::Histogram(bucket) => return write_histogram(search, bucket, out),
+    };
+    let mut file_counts = search.file_counts.lock().unwrap().clone();
+    // The most matches first, and files with as many in path order
+    file_counts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+    file_counts.truncate(*top);
+    let width = file_counts
+        .first()
+        .map_or(1, |(_, count)| count.to_string().len());
+    writeln!(out, "top {} files by matches:", file_counts.len())
+        .map_err(SearchError::WriteError)?;
+    for (path, count) in file_counts {
+        writeln!(out, "{count:>width$} {}", path.display()).map_err(SearchError::WriteError)?;
+    }
+    Ok(())
+}
+
+// One line per minute or hour from the first timestamp to the last, with a bar as long as its
+// share of the busiest one. Hours or minutes without matches are printed too, so the bars show
+// how matches spread over time, unless there would be too many of them.
+fn write_histogram<W: Write>(
+    search: &Search,
+    bucket: &Bucket,
+    out: &mut W,
+) -> Result<(), SearchError> {
+    const MAX_BUCKETS: i64 = 10_000;
+    const BAR_WIDTH: usize = 40;
+    let timeline = search.timeline.lock().unwrap();
+    let undated = timeline.get(&None).copied().unwrap_or(0);
+    let dated: Vec<(i64, usize)> = timeline
+        .iter()
+        .filter_map(|(time, count)| time.map(|time| (time, *count)))
+        .collect();
+    let unit = match bucket {
+        Bucket::Minute => "minute",
+        Bucket::Hour => "hour",
+    };
+    writeln!(out, "matches per {unit}:").map_err(SearchError::WriteError)?;
+    let rows: Vec<(i64, usize)> = match (dated.first(), dated.last()) {
+        (Some((first, _)), Some((last, _))) if last - first < MAX_BUCKETS => (*first..=*last)
+            .map(|time| (time, timeline.get(&Some(time)).copied().unwrap_or(0)))
+            .collect(),
+        _ => dated,
+    };
+    let busiest = rows.iter().map(|(_, count)| *count).max().unwrap_or(0);
+    let width = busiest.to_string().len();
+    for (time, count) in rows {
+        let bar = "#".repeat((count * BAR_WIDTH).div_ceil(busiest.max(1)));
+        let line = format!("{} {count:>width$} {bar}", histogram::label(time, bucket));
+        writeln!(out, "{}", line.trim_end()).map_err(SearchError::WriteError)?;
+    }
+    if undated > 0 {
+        writeln!(out, "{undated} without a timestamp").map_err(SearchError::WriteError)?;
+    }
+    Ok(())
+}
+
+// The `--stats` report, with how much of the run each matcher thread spent matching
+pub(crate) fn write_stats<E: Write>(
+    search: &Search,
+    elapsed: Duration,
+    err_out: &mut E,
+) -> Result<(), SearchError> {
+    let stats = &search.stats;
+    let counters = [
+        ("files searched", stats.files.load(Ordering::SeqCst)),
+        ("files skipped", stats.skipped.lock().unwrap().len()),
+        ("bytes scanned", stats.bytes.load(Ordering::SeqCst)),
+        ("lines scanned", stats.lines.load(Ordering::SeqCst)),
+        ("matches found", search.match_count.load(Ordering::SeqCst)),
+    ];
+    for (name, value) in counters {
+        writeln!(err_out, "{name}: {value}").map_err(SearchError::WriteError)?;
+    }
+    writeln!(err_out, "elapsed: {:.3}s", elapsed.as_secs_f64()).map_err(SearchError::WriteError)?;
+    for (thread, busy) in stats.busy.lock().unwrap().iter().enumerate() {
+        let utilization = match elapsed.is_zero() {
+            true => 0.0,
+            false => 100.0 * busy.as_secs_f64() / elapsed.as_secs_f64(),
+        };
+        writeln!(
+            err_out,
+            "thread {} busy: {:.3}s ({utilization:.0}%)",
+            thread + 1,
+            busy.as_secs_f64()
+        )
+        .map_err(SearchError::WriteError)?;
+    }
+    Ok(())
+}
+
+// The same statistics as one JSON document, with the reason each file was skipped, on a line
+// of its own
+pub(crate) fn write_stats_json<E: Write>(
+    search: &Search,
+    elapsed: Duration,
+    err_out: &mut E,
+) -> Result<(), SearchError> {
+    let stats = &search.stats;
+    let skipped: Vec<serde_json::Value> = stats
+        .skipped
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(path, reason)| json!({ "path": path.display().to_string(), "reason": reason }))
+        .collect();
+    let threads: Vec<serde_json::Value> = stats
+        .busy
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|busy| json!({ "busy_seconds": busy.as_secs_f64() }))
+        .collect();
+    let document = json!({
+        "files_searched": stats.files.load(Ordering::SeqCst),
+        "files_skipped": skipped,
+        "bytes_scanned": stats.bytes.load(Ordering::SeqCst),
+        "lines_scanned": stats.lines.load(Ordering::SeqCst),
+        "matches_found": search.match_count.load(Ordering::SeqCst),
+        "elapsed_seconds": elapsed.as_secs_f64(),
+        "threads": threads,
+    });
+    writeln!(err_out, "{document}").map_err(SearchError::WriteError)
+}
@@ -0,0 +1,362 @@
+use std::io::{BufRead, Error, ErrorKind, Read};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, KeyInit, Mac};
+use quick_xml::escape;
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use sha2::{Digest, Sha256};
+
+// The hash of an empty payload, which every GET has
+const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+// Keys asked for with each page of a listing, the most S3 gives
+const PAGE_SIZE: usize = 1000;
+
+// A bucket, and the prefix of the keys searched in it, from `s3://bucket/prefix`
+pub(crate) struct Location {
+    pub(crate) bucket: String,
+    pub(crate) prefix: String,
+}
+
+impl Location {
+    pub(crate) fn parse(url: &str) -> std::io::Result<Location> {
+        let rest = url.strip_prefix("s3://").unwrap_or(url);
+        let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+        if bucket.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("URL {url} has no bucket"),
+            ));
+        }
+        Ok(Location {
+            bucket: bucket.to_string(),
+            prefix: prefix.to_string(),
+        })
+    }
+}
+
+// An object found by a listing
+pub(crate) struct Object {
+    pub(crate) key: String,
+    pub(crate) size: Option<u64>,
+    pub(crate) modified: Option<SystemTime>,
+}
+
+// A page of a listing: its objects, and whether more pages follow it, from the token given
+#[derive(Default)]
+struct Page {
+    objects: Vec<Object>,
+    truncated: bool,
+    next: Option<String>,
+}
+
+impl Page {
+    // Read from the `ListBucketResult` document S3 answers with
+    fn parse(xml: &str) -> std::io::Result<Page> {
+        let invalid = |reason: String| {
+            Error::new(ErrorKind::InvalidData, format!("invalid listing: {reason}"))
+        };
+        let mut reader = Reader::from_str(xml);
+        let mut page = Page::default();
+        // The names of the elements the reader is in, the text of the innermost one, and the
+        // object of the `Contents` element it is in
+        let mut within = Vec::new();
+        let mut text = String::new();
+        let mut object = None;
+        loop {
+            match reader
+                .read_event()
+                .map_err(|err| invalid(err.to_string()))?
+            {
+                Event::Start(element) => {
+                    let name = element.local_name().as_ref().to_string();
+                    if within.len() == 1 && name == "Contents" {
+                        object = Some(Object {
+                            key: String::new(),
+                            size: None,
+                            modified: None,
+                        });
+                    }
+                    within.push(name);
+                    text.clear();
+                }
+                Event::Text(content) => text.push_str(&content.xml10_content()),
+                Event::GeneralRef(reference) => {
+                    let resolved = match reference
+                        .resolve_char_ref()
+                        .map_err(|err| invalid(err.to_string()))?
+                    {
+                        Some(char) => char.to_string(),
+                        None => {
+                            let name = reference.xml10_content();
+                            escape::resolve_predefined_entity(&name)
+                                .ok_or_else(|| invalid(format!("unknown entity &{name};")))?
+                                .to_string()
+                        }
+                    };
+                    text.push_str(&resolved);
+                }
+                Event::End(_) => {
+                    let name = within.pop().unwrap_or_default();
+                    let value = std::mem::take(&mut text);
+                    match (within.len(), name.as_str()) {
+                        (1, "Contents") => page.objects.extend(object.take()),
+                        (1, "IsTruncated") => page.truncated = value == "true",
+                        (1, "NextContinuationToken") => page.next = Some(value),
+                        (2, field) if let Some(object) = &mut object => match field {
+                            "Key" => object.key = value,
+                            "Size" => object.size = value.parse().ok(),
+                            "LastModified" => object.modified = parse_time(&value),
+                            _ => (),
+                        },
+                        _ => (),
+                    }
+                }
+                Event::Eof => return Ok(page),
+                _ => (),
+            }
+        }
+    }
+}
+
+pub(crate) struct Credentials {
+    pub(crate) access_key: String,
+    pub(crate) secret_key: String,
+    // Temporary credentials come with a session token
+    pub(crate) session_token: Option<String>,
+}
+
+// An S3 API to list and read objects through, AWS's own or another one, like MinIO's, at
+// `endpoint`. Requests are signed with Signature Version 4 when there are credentials, read
+// from the environment like the AWS CLI does; without them, only public buckets can be read.
+pub(crate) struct Store {
+    endpoint: Option<String>,
+    region: String,
+    credentials: Option<Credentials>,
+}
+
+impl Store {
+    pub(crate) fn new(endpoint: Option<&str>) -> Store {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let credentials = match (var("AWS_ACCESS_KEY_ID"), var("AWS_SECRET_ACCESS_KEY")) {
+            (Some(access_key), Some(secret_key)) => Some(Credentials {
+                access_key,
+                secret_key,
+                session_token: var("AWS_SESSION_TOKEN"),
+            }),
+            _ => None,
+        };
+        Store {
+            endpoint: endpoint
+                .map(str::to_string)
+                .or_else(|| var("AWS_ENDPOINT_URL_S3"))
+                .or_else(|| var("AWS_ENDPOINT_URL"))
+                .map(|endpoint| endpoint.trim_end_matches('/').to_string()),
+            region: var("AWS_REGION")
+                .or_else(|| var("AWS_DEFAULT_REGION"))
+                .unwrap_or_else(|| "us-east-1".to_string()),
+            credentials,
+        }
+    }
+
+    // Lists the objects under the location's prefix, page by page, handing each to `on_object`
+    // until it returns false. Keys ending in `/`, which consoles make to stand for folders,
+    // aren't objects to search.
+    pub(crate) fn list(
+        &self,
+        location: &Location,
+        on_object: &mut dyn FnMut(Object) -> bool,
+    ) -> std::io::Result<()> {
+        let mut token = None;
+        loop {
+            let mut query = vec![
+                ("list-type".to_string(), "2".to_string()),
+                ("max-keys".to_string(), PAGE_SIZE.to_string()),
+                ("prefix".to_string(), location.prefix.clone()),
+            ];
+            if let Some(token) = token.take() {
+                query.push(("continuation-token".to_string(), token));
+            }
+            let mut xml = String::new();
+            self.get(&location.bucket, "", &query)?
+                .read_to_string(&mut xml)?;
+            let page = Page::parse(&xml)?;
+            for object in page.objects {
+                if object.key.ends_with('/') {
+                    continue;
+                }
+                if !on_object(object) {
+                    return Ok(());
+                }
+            }
+            match (page.truncated, page.next) {
+                (false, _) => return Ok(()),
+                (true, Some(next)) => token = Some(next),
+                (true, None) => return Err(Error::other("the listing has no continuation token")),
+            }
+        }
+    }
+
+    // Reads an object as it is fetched
+    pub(crate) fn open(&self, bucket: &str, key: &str) -> std::io::Result<Box<dyn BufRead + Send>> {
+        self.get(bucket, key, &[])
+    }
+
+    fn get(
+        &self,
+        bucket: &str,
+        key: &str,
+        query: &[(String, String)],
+    ) -> std::io::Result<Box<dyn BufRead + Send>> {
+        // Buckets are reached by their own host name, unless the name has dots, which the
+        // certificate of `*.s3.amazonaws.com` doesn't cover, or on another endpoint
+        let (base, path) = match &self.endpoint {
+            Some(endpoint) => (endpoint.clone(), format!("/{bucket}/{key}")),
+            None if bucket.contains('.') => (
+                format!("https://s3.{}.amazonaws.com", self.region),
+                format!("/{bucket}/{key}"),
+            ),
+            None => (
+                format!("https://{bucket}.s3.{}.amazonaws.com", self.region),
+                format!("/{key}"),
+            ),
+        };
+        let path = encode(&path, false);
+        let mut query: Vec<(String, String)> = query
+            .iter()
+            .map(|(name, value)| (encode(name, true), encode(value, true)))
+            .collect();
+        query.sort();
+        let query = query
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join("&");
+        let url = match query.is_empty() {
+            true => format!("{base}{path}"),
+            false => format!("{base}{path}?{query}"),
+        };
+        let mut headers = Vec::new();
+        if let Some(credentials) = &self.credentials {
+            let host = base
+                .split_once("://")
+                .map_or(base.as_str(), |(_, host)| host);
+            let host = host.split('/').next().unwrap_or(host);
+            // The Host header leaves out the scheme's own port, so the signature must too
+            let host = host
+                .strip_suffix(":80")
+                .filter(|_| base.starts_with("http://"))
+                .or_else(|| {
+                    host.strip_suffix(":443")
+                        .filter(|_| base.starts_with("https://"))
+                })
+                .unwrap_or(host);
+            headers.push(("x-amz-content-sha256".to_string(), EMPTY_SHA256.to_string()));
+            headers.push(("x-amz-date".to_string(), amz_date(SystemTime::now())));
+            if let Some(token) = &credentials.session_token {
+                headers.push(("x-amz-security-token".to_string(), token.clone()));
+            }
+            let mut signed = headers.clone();
+            signed.push(("host".to_string(), host.to_string()));
+            let authorization = authorization(credentials, &self.region, &path, &query, &signed);
+            headers.push(("authorization".to_string(), authorization));
+        }
+        crate::http::get(&url, &headers)
+    }
+}
+
+// The Authorization header of a GET, signed with Signature Version 4 for S3. `path` and `query`
+// are already URI-encoded, and `headers` are all those signed, with lowercase names and the
+// date among them.
+pub(crate) fn authorization(
+    credentials: &Credentials,
+    region: &str,
+    path: &str,
+    query: &str,
+    headers: &[(String, String)],
+) -> String {
+    let mut headers = headers.to_vec();
+    headers.sort();
+    let find = |name: &str| {
+        headers
+            .iter()
+            .find(|(found, _)| found == name)
+            .map_or("", |(_, value)| value.as_str())
+    };
+    let date = find("x-amz-date");
+    let payload = find("x-amz-content-sha256");
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{name}:{}\n", value.trim()))
+        .collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_request =
+        format!("GET\n{path}\n{query}\n{canonical_headers}\n{signed_headers}\n{payload}");
+    let scope = format!("{}/{region}/s3/aws4_request", &date[..date.len().min(8)]);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{date}\n{scope}\n{}",
+        hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+    let mut key = format!("AWS4{}", credentials.secret_key).into_bytes();
+    for part in [&date[..date.len().min(8)], region, "s3", "aws4_request"] {
+        key = hmac_sha256(&key, part.as_bytes());
+    }
+    let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+        credentials.access_key
+    )
+}
+
+// `20130524T000000Z`, the form of a request's date that is signed
+fn amz_date(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let (year, month, day) = crate::template::civil_from_days(seconds.div_euclid(86_400));
+    let second_of_day = seconds.rem_euclid(86_400);
+    format!(
+        "{year:04}{month:02}{day:02}T{:02}{:02}{:02}Z",
+        second_of_day / 3600,
+        second_of_day / 60 % 60,
+        second_of_day % 60
+    )
+}
+
+// When an object was last modified, as listings give it: `2009-10-12T17:50:30.000Z`
+fn parse_time(text: &str) -> Option<SystemTime> {
+    let number = |range: std::ops::Range<usize>| text.get(range)?.parse::<i64>().ok();
+    let days = crate::histogram::days_from_civil(number(0..4)?, number(5..7)?, number(8..10)?);
+    let seconds = days * 86_400 + number(11..13)? * 3600 + number(14..16)? * 60 + number(17..19)?;
+    Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(seconds).ok()?))
+}
+
+// URI-encodes all but the unreserved characters, as signing wants it, and keeps `/` in paths
+fn encode(text: &str, slash: bool) -> String {
+    let mut encoded = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if !slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
@@ -0,0 +1,76 @@
+use crate::SearchError;
+use std::path::Path;
+use tree_sitter::{Language, Parser, Query, QueryCursor, StreamingIterator};
+
+// A tree-sitter query matched against the syntax tree of each source file, e.g.
+// `((function_item name: (identifier) @name) (#eq? @name "init"))`. The language of a file comes
+// from its extension, and files in other languages never match.
+pub(crate) struct StructuralQuery {
+    // Node kinds differ between grammars, so the query is compiled for each language it fits
+    languages: Vec<(&'static [&'static str], Language, Query)>,
+}
+
+impl StructuralQuery {
+    pub(crate) fn new(source: &str) -> Result<StructuralQuery, SearchError> {
+        let grammars: [(&'static [&'static str], Language); 2] = [
+            (&["rs"], tree_sitter_rust::LANGUAGE.into()),
+            (&["py", "pyi"], tree_sitter_python::LANGUAGE.into()),
+        ];
+        let mut languages = Vec::new();
+        let mut first_error = None;
+        for (extensions, language) in grammars {
+            match Query::new(&language, source) {
+                Ok(query) => languages.push((extensions, language, query)),
+                Err(err) => {
+                    first_error.get_or_insert(err);
+                }
+            }
+        }
+        match first_error {
+            // A query naming Rust node kinds is invalid for Python and vice versa, so it only
+            // has to compile for one of the languages
+            Some(err) if languages.is_empty() => Err(SearchError::InitializationError(format!(
+                "invalid structural query: {err}"
+            ))),
+            _ => Ok(StructuralQuery { languages }),
+        }
+    }
+
+    // The 0-based rows on which the query matches start, in order and each only once
+    pub(crate) fn matching_rows(&self, path: &Path, source: &[u8]) -> Vec<usize> {
+        let Some(extension) = path.extension().and_then(|extension| extension.to_str()) else {
+            return Vec::new();
+        };
+        let Some((_, language, query)) = self
+            .languages
+            .iter()
+            .find(|(extensions, _, _)| extensions.contains(&extension))
+        else {
+            return Vec::new();
+        };
+        let mut parser = Parser::new();
+        if parser.set_language(language).is_err() {
+            return Vec::new();
+        }
+        let Some(tree) = parser.parse(source, None) else {
+            return Vec::new();
+        };
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(query, tree.root_node(), source);
+        let mut rows = Vec::new();
+        while let Some(found) = matches.next() {
+            // A match starts where its earliest captured node does
+            if let Some(row) = found
+                .captures()
+                .iter()
+                .map(|capture| capture.node.start_position().row)
+                .min()
+            {
+                rows.push(row);
+            }
+        }
+        rows.sort_unstable();
+        rows.dedup();
+        rows
+    }
+}
@@ -0,0 +1,18 @@
+use std::path::Path;
+
+// Archives are told by their name: `.tar`, or `.tar.gz` and `.tgz`. Those compressed with the
+// formats of `search_zip` are archives too when it is on. They are read with the `tar` crate,
+// in ustar, GNU or pax format.
+pub(crate) fn is_tar(path: &Path, search_zip: bool) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    let compressed: &[&str] = match search_zip {
+        true => &[".tar.zst", ".tar.xz", ".txz", ".tar.bz2", ".tbz2"],
+        false => &[],
+    };
+    [".tar", ".tar.gz", ".tgz"]
+        .iter()
+        .chain(compressed)
+        .any(|extension| name.ends_with(extension))
+}
@@ -0,0 +1,147 @@
+use crate::{SearchError, SearchMatch};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// A line of output written with placeholders, e.g. `{path}:{line}:{column}: {text}`.
+// `{{` and `}}` stand for literal braces. Placeholders with nothing to show, like the column of
+// a context line, are left empty.
+pub(crate) struct Template {
+    parts: Vec<Part>,
+}
+
+enum Part {
+    Literal(String),
+    Path,
+    Line,
+    Column,
+    Text,
+    // The text of the first match on the line
+    Match,
+    Pattern,
+    // The byte offset of the line in its file, or of the match with byte patterns
+    Offset,
+    // When the file was last modified, in UTC
+    Mtime,
+}
+
+// What a rendered line needs besides the match itself
+pub(crate) struct Fields<'a> {
+    pub(crate) first_match: Option<&'a str>,
+    pub(crate) modified: Option<SystemTime>,
+}
+
+impl Template {
+    pub(crate) fn parse(source: &str) -> Result<Template, SearchError> {
+        let invalid = |reason: &str| {
+            SearchError::InitializationError(format!("format {source} is invalid: {reason}"))
+        };
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = source.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '}' => return Err(invalid("unmatched '}', write '}}' for a brace")),
+                '{' => {
+                    let name: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                    let part = match name.as_str() {
+                        "path" => Part::Path,
+                        "line" => Part::Line,
+                        "column" => Part::Column,
+                        "text" => Part::Text,
+                        "match" => Part::Match,
+                        "pattern" => Part::Pattern,
+                        "offset" => Part::Offset,
+                        "mtime" => Part::Mtime,
+                        _ => return Err(invalid(&format!("unknown placeholder {{{name}}}"))),
+                    };
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(part);
+                }
+                _ => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Template { parts })
+    }
+
+    pub(crate) fn uses_mtime(&self) -> bool {
+        self.parts.iter().any(|part| matches!(part, Part::Mtime))
+    }
+
+    pub(crate) fn render(&self, search_match: &SearchMatch, fields: &Fields) -> String {
+        let mut rendered = String::new();
+        for part in self.parts.iter() {
+            match part {
+                Part::Literal(text) => rendered.push_str(text),
+                Part::Path => rendered.push_str(&search_match.path.display().to_string()),
+                Part::Line => rendered.push_str(&search_match.line_number.to_string()),
+                Part::Column => {
+                    if let Some(column) = search_match.column {
+                        rendered.push_str(&column.to_string());
+                    }
+                }
+                Part::Text => rendered.push_str(&search_match.line),
+                Part::Match => rendered.push_str(fields.first_match.unwrap_or_default()),
+                Part::Pattern => {
+                    rendered.push_str(search_match.pattern.as_deref().unwrap_or_default())
+                }
+                Part::Offset => {
+                    if let Some(offset) = search_match.byte_offset.or(search_match.line_offset) {
+                        rendered.push_str(&offset.to_string());
+                    }
+                }
+                Part::Mtime => {
+                    if let Some(modified) = fields.modified {
+                        rendered.push_str(&utc_timestamp(modified));
+                    }
+                }
+            }
+        }
+        rendered
+    }
+}
+
+// ISO 8601 in UTC, e.g. `2024-03-01T12:30:00Z`
+fn utc_timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let (days, day_seconds) = (seconds / 86_400, seconds % 86_400);
+    let (year, month, day) = civil_from_days(days as i64);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        day_seconds / 3600,
+        day_seconds % 3600 / 60,
+        day_seconds % 60
+    )
+}
+
+// The proleptic Gregorian date of a count of days since the Unix epoch, after Howard Hinnant's
+// `civil_from_days`
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    (year_of_era + era * 400 + i64::from(month <= 2), month, day)
+}
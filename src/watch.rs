@@ -0,0 +1,106 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, channel};
+use std::time::Duration;
+
+use notify::event::{AccessKind, AccessMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher as _};
+
+// How often to look for changes without notifications
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+// How long to let a burst of changes, like a build writing its output, settle before searching
+const SETTLE_TIME: Duration = Duration::from_millis(100);
+
+// Waits for something to change under the watched paths, notified by the `notify` crate:
+// through inotify on Linux, FSEvents on macOS and the like elsewhere. When they can't be
+// watched, as once there are more directories than inotify can watch, it just waits a while,
+// and the caller finds whatever changed meanwhile.
+pub(crate) struct Watcher {
+    roots: Vec<PathBuf>,
+    // Whether changes in hidden directories count
+    hidden: bool,
+    // Kept for as long as the paths are watched, with the events it sends
+    notified: Option<(RecommendedWatcher, Receiver<notify::Result<Event>>)>,
+}
+
+impl Watcher {
+    pub(crate) fn new(roots: &[PathBuf], hidden: bool) -> Watcher {
+        let (sender, events) = channel();
+        let notified = notify::recommended_watcher(sender)
+            .ok()
+            .and_then(|mut watcher| {
+                for root in roots {
+                    let watched = match root.is_dir() {
+                        true => watcher.watch(root, RecursiveMode::Recursive),
+                        // A file is watched through its directory, which sees it replaced too
+                        false => watcher.watch(&parent_of(root), RecursiveMode::NonRecursive),
+                    };
+                    // Out of watches, so changes are looked for every so often instead
+                    watched.ok()?;
+                }
+                Some((watcher, events))
+            });
+        Watcher {
+            roots: roots.to_vec(),
+            hidden,
+            notified,
+        }
+    }
+
+    // Returns once something may have changed
+    pub(crate) fn wait(&mut self) {
+        let Some((_, events)) = &self.notified else {
+            std::thread::sleep(POLL_INTERVAL);
+            return;
+        };
+        loop {
+            match events.recv() {
+                Ok(Ok(event)) if !self.counts(&event) => continue,
+                // Events that were missed, like those of an overflowing queue, are changes too
+                Ok(_) => break,
+                Err(_) => {
+                    self.notified = None;
+                    std::thread::sleep(POLL_INTERVAL);
+                    return;
+                }
+            }
+        }
+        std::thread::sleep(SETTLE_TIME);
+        // The events that came meanwhile only matter for having happened
+        while events.try_recv().is_ok() {}
+    }
+
+    // Whether an event may have changed a file that is searched: files being opened and read,
+    // by the search itself too, don't; neither do changes under git's or, unless asked for,
+    // hidden directories.
+    fn counts(&self, event: &Event) -> bool {
+        let changes = match event.kind {
+            EventKind::Access(AccessKind::Close(AccessMode::Write)) => true,
+            EventKind::Access(_) => false,
+            _ => true,
+        };
+        changes
+            && event
+                .paths
+                .iter()
+                .any(|path| !self.roots.iter().any(|root| self.is_skipped(root, path)))
+    }
+
+    fn is_skipped(&self, root: &Path, path: &Path) -> bool {
+        let Ok(below) = path.strip_prefix(root) else {
+            return false;
+        };
+        // The last component is the file itself, which is searched if it is hidden
+        let mut dirs = below.components().rev().skip(1);
+        dirs.any(|dir| {
+            let name = dir.as_os_str();
+            name == ".git" || (!self.hidden && name.as_encoded_bytes().starts_with(b"."))
+        })
+    }
+}
+
+fn parent_of(file: &Path) -> PathBuf {
+    match file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
@@ -0,0 +1,57 @@
+use std::io::{Error, Read, Seek};
+use std::path::Path;
+
+use ::zip::ZipArchive;
+
+// Zip files are told by their name, which for Java archives, office documents and ebooks isn't
+// `.zip`
+pub(crate) fn is_zip(path: &Path) -> bool {
+    let Some(extension) = path.extension().and_then(|extension| extension.to_str()) else {
+        return false;
+    };
+    [
+        "zip", "jar", "war", "ear", "apk", "docx", "xlsx", "pptx", "odt", "ods", "odp", "epub",
+    ]
+    .contains(&extension.to_ascii_lowercase().as_str())
+}
+
+// Reads the files of a zip archive, listed by its central directory, in the order they are
+// listed. Members are stored or deflated, or compressed with bzip2, zstd or LZMA when the build
+// has the feature for it; zip64 archives, for members or archives over 4 GiB, are read too.
+pub(crate) struct ZipReader<R> {
+    archive: ZipArchive<R>,
+    // The index in the archive and name of each file, leaving out directories
+    files: Vec<(usize, String)>,
+}
+
+impl<R: Read + Seek> ZipReader<R> {
+    pub(crate) fn new(input: R) -> Result<ZipReader<R>, Error> {
+        let archive = ZipArchive::new(input)?;
+        let mut files = Vec::new();
+        for index in 0..archive.len() {
+            let Some(name) = archive.name_for_index(index) else {
+                continue;
+            };
+            let name = name?;
+            // Directories have entries of their own, with names ending in `/`
+            if !name.ends_with('/') {
+                files.push((index, name.into_owned()));
+            }
+        }
+        Ok(ZipReader { archive, files })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub(crate) fn name(&self, index: usize) -> &str {
+        &self.files[index].1
+    }
+
+    // The contents of a member, decompressed. Its checksum is checked once it is read through.
+    pub(crate) fn open(&mut self, index: usize) -> Result<Box<dyn Read + '_>, Error> {
+        let member = self.archive.by_index(self.files[index].0)?;
+        Ok(Box::new(member))
+    }
+}